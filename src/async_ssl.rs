@@ -1,14 +1,16 @@
 use core::ffi::CStr;
-use core::task::{Poll, Context, ready};
+use core::net::IpAddr;
+use core::future::poll_fn;
+use core::task::{Poll, Context, Waker, ready};
 use core::pin::Pin;
 
-use std::io;
+use std::io::{self, ErrorKind};
 
-use tokio::net::TcpStream;
 use tokio::io::{AsyncRead, ReadBuf, AsyncWrite};
 use pin_project_lite::pin_project;
 
-use crate::{SslCtx, Ssl, ErrorStack, SslError};
+use crate::{SslCtx, Ssl, ErrorStack, SslError, VerifyMode, X509, X509Ref, X509NameRef, X509StoreContextRef, X509VerifyError, PKey};
+use crate::{TlsVersion, SslCipherRef, SignatureAlgorithm, SslSession, ShutdownState};
 
 pin_project! {
     /// Async version of [`Ssl`], implements [`tokio::io::AsyncRead`] and [`tokio::io::AsyncWrite`]
@@ -28,20 +30,47 @@ pin_project! {
     /// ssl.shutdown().await?;
     /// ```
     /// Async version DOES NOT close the connection automatically!
-    /// Always make sure that you have closed it by calling `ssl.shutdown().await`
+    /// Always make sure that you have closed it by calling `ssl.shutdown().await`,
+    /// which sends `close_notify` and shuts down the write half of the stream
+    ///
+    /// Works over any byte stream: TCP and Unix sockets, [`tokio::io::duplex`] pipes, tunnels, etc.
+    /// Encrypted data is buffered in memory, and written records are sent before the next operation
     #[derive(Debug)]
-    pub struct AsyncSsl {
+    pub struct AsyncSsl<S> {
         ssl: Ssl,
-        stream: TcpStream,
+        #[pin]
+        stream: S,
+        // Encrypted data taken from OpenSSL but not yet written to the stream
+        out: Vec<u8>,
+        // Writer waiting for `out` to drain, in case the reader half drains it
+        write_waker: Option<Waker>,
+        // Failure to send `close_notify`, returned once the stream is shut down
+        shutdown_error: Option<io::Error>,
     }
 }
 
-impl AsyncSsl {
-    /// Constructs a new async SSL
-    pub fn new(ctx: &SslCtx, stream: TcpStream) -> Result<AsyncSsl, ErrorStack> {
-        let mut ssl = Ssl::new(ctx)?;
-        ssl.set_fd(&stream)?;
-        Ok(AsyncSsl { ssl, stream })
+impl<S: AsyncRead + AsyncWrite> AsyncSsl<S> {
+    /// Constructs a new async SSL over the stream
+    pub fn new(ctx: &SslCtx, stream: S) -> Result<AsyncSsl<S>, ErrorStack> {
+        AsyncSsl::from_ssl(Ssl::new(ctx)?, stream)
+    }
+
+    /// Constructs a new async SSL over the stream from an already configured SSL object
+    pub fn from_ssl(mut ssl: Ssl, stream: S) -> Result<AsyncSsl<S>, ErrorStack> {
+        ssl.set_mem_bios()?;
+        Ok(AsyncSsl { ssl, stream, out: vec![], write_waker: None, shutdown_error: None })
+    }
+
+    /// Returns a reference to the underlying stream
+    pub fn get_ref(&self) -> &S {
+        &self.stream
+    }
+
+    /// Returns a mutable reference to the underlying stream
+    ///
+    /// Reading or writing it directly will corrupt the TLS connection
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.stream
     }
 
     /// Sets the hostname for verification
@@ -49,74 +78,295 @@ impl AsyncSsl {
         self.ssl.set_hostname(hostname)
     }
 
-    /// Performs the connection as a client
-    pub async fn connect(&mut self) -> Result<(), SslError> {
+    /// Sets the IP address expected in the peer certificate, without sending SNI
+    pub fn set_verify_ip(&mut self, ip: IpAddr) -> Result<(), ErrorStack> {
+        self.ssl.set_verify_ip(ip)
+    }
+
+    /// Returns the current session, which can be saved for resumption
+    pub fn session(&self) -> Option<SslSession> {
+        self.ssl.session()
+    }
+
+    /// Sets the session to resume, must be called before [`AsyncSsl::connect`]
+    pub fn set_session(&mut self, session: &SslSession) -> Result<(), ErrorStack> {
+        self.ssl.set_session(session)
+    }
+
+    /// Overrides the minimal protocol version of the context for this connection
+    pub fn set_min_version(&mut self, ver: Option<TlsVersion>) -> Result<(), ErrorStack> {
+        self.ssl.set_min_version(ver)
+    }
+
+    /// Overrides the maximal protocol version of the context for this connection
+    pub fn set_max_version(&mut self, ver: Option<TlsVersion>) -> Result<(), ErrorStack> {
+        self.ssl.set_max_version(ver)
+    }
+
+    /// Returns the minimal protocol version, or `None` if there is no bound
+    pub fn min_version(&self) -> Option<TlsVersion> {
+        self.ssl.min_version()
+    }
+
+    /// Returns the maximal protocol version, or `None` if there is no bound
+    pub fn max_version(&self) -> Option<TlsVersion> {
+        self.ssl.max_version()
+    }
+
+    /// Overrides TLS 1.3 cipher suites of the context for this connection
+    pub fn set_ciphersuites(&mut self, list: &CStr) -> Result<(), ErrorStack> {
+        self.ssl.set_ciphersuites(list)
+    }
+
+    /// Overrides key exchange groups of the context for this connection
+    pub fn set_groups_list(&mut self, list: &CStr) -> Result<(), ErrorStack> {
+        self.ssl.set_groups_list(list)
+    }
+
+    /// Overrides handshake signature algorithms of the context for this connection
+    pub fn set_sigalgs_list(&mut self, list: &CStr) -> Result<(), ErrorStack> {
+        self.ssl.set_sigalgs_list(list)
+    }
+
+    /// Overrides client certificate signature algorithms of the context for this connection
+    pub fn set_client_sigalgs_list(&mut self, list: &CStr) -> Result<(), ErrorStack> {
+        self.ssl.set_client_sigalgs_list(list)
+    }
+
+    /// Overrides certificate verification flags of the context for this connection
+    pub fn set_verify_mode(&mut self, mode: VerifyMode) {
+        self.ssl.set_verify_mode(mode)
+    }
+
+    /// Returns certificate verification flags of this connection
+    pub fn verify_mode(&self) -> VerifyMode {
+        self.ssl.verify_mode()
+    }
+
+    /// Overrides the verify callback of the context for this connection
+    pub fn set_verify_callback<F>(&mut self, callback: F)
+    where F: Fn(bool, &X509StoreContextRef) -> bool + Send + Sync + 'static {
+        self.ssl.set_verify_callback(callback)
+    }
+
+    /// Sets the certificate presented to the peer, e.g. the client certificate for mutual TLS
+    pub fn use_certificate(&mut self, cert: &X509Ref) -> Result<(), ErrorStack> {
+        self.ssl.use_certificate(cert)
+    }
+
+    /// Appends an intermediate certificate to the chain sent to the peer
+    pub fn add_chain_certificate(&mut self, cert: &X509Ref) -> Result<(), ErrorStack> {
+        self.ssl.add_chain_certificate(cert)
+    }
+
+    /// Sets the private key matching the certificate
+    pub fn use_private_key(&mut self, key: &PKey) -> Result<(), ErrorStack> {
+        self.ssl.use_private_key(key)
+    }
+
+    /// Returns the certificate presented by the peer, if any
+    pub fn peer_certificate(&self) -> Option<X509> {
+        self.ssl.peer_certificate()
+    }
+
+    /// Returns the certificate chain sent by the peer, as is
+    ///
+    /// Check [`Ssl::peer_cert_chain`] for details
+    pub fn peer_cert_chain(&self) -> Vec<&X509Ref> {
+        self.ssl.peer_cert_chain()
+    }
+
+    /// Returns the verified chain from the peer certificate up to the trusted root
+    pub fn verified_chain(&self) -> Vec<&X509Ref> {
+        self.ssl.verified_chain()
+    }
+
+    /// Returns the CA names the server advertised when requesting the client certificate
+    pub fn client_ca_names(&self) -> Vec<&X509NameRef> {
+        self.ssl.client_ca_names()
+    }
+
+    /// Returns the result of peer certificate verification
+    pub fn verify_result(&self) -> Result<(), X509VerifyError> {
+        self.ssl.verify_result()
+    }
+
+    /// Returns the negotiated protocol version
+    pub fn version(&self) -> Option<TlsVersion> {
+        self.ssl.version()
+    }
+
+    /// Returns the negotiated cipher suite
+    pub fn current_cipher(&self) -> Option<&SslCipherRef> {
+        self.ssl.current_cipher()
+    }
+
+    /// Returns the name of the negotiated key exchange group
+    pub fn negotiated_group(&self) -> Option<&str> {
+        self.ssl.negotiated_group()
+    }
+
+    /// Returns the algorithm the peer used to sign the handshake
+    pub fn peer_signature_algorithm(&self) -> Option<SignatureAlgorithm> {
+        self.ssl.peer_signature_algorithm()
+    }
+
+    /// Returns true if the session was resumed instead of a full handshake
+    pub fn session_reused(&self) -> bool {
+        self.ssl.session_reused()
+    }
+
+    /// Returns the server name sent via SNI
+    pub fn servername(&self) -> Option<&str> {
+        self.ssl.servername()
+    }
+
+    /// Returns the protocol selected via ALPN, if any
+    pub fn selected_alpn_protocol(&self) -> Option<&[u8]> {
+        self.ssl.selected_alpn_protocol()
+    }
+
+    /// Returns which sides have closed the connection with `close_notify`
+    pub fn shutdown_state(&self) -> ShutdownState {
+        self.ssl.shutdown_state()
+    }
+
+    /// Writes encrypted data buffered so far to the stream
+    fn poll_flush_out(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let mut me = self.project();
         loop {
-            let ret = self.ssl.connect();
-            match ret {
-                Err(SslError::WantRead) => self.stream.readable().await?,
-                Err(SslError::WantWrite) => self.stream.writable().await?,
-                other => return other,
+            if me.out.is_empty() {
+                me.ssl.take_output(me.out);
             }
+            if me.out.is_empty() {
+                // The stream only wakes the last task that polled it, which may be the reader half
+                if let Some(waker) = me.write_waker.take() && !waker.will_wake(cx.waker()) {
+                    waker.wake();
+                }
+                return Poll::Ready(Ok(()));
+            }
+            let n = ready!(me.stream.as_mut().poll_write(cx, me.out))?;
+            if n == 0 { return Poll::Ready(Err(ErrorKind::WriteZero.into())); }
+            me.out.drain(..n);
         }
     }
 
-    /// Accepts the connection as a server
-    pub async fn accept(&mut self) -> Result<(), SslError> {
-        loop {
-            let ret = self.ssl.accept();
-            match ret {
-                Err(SslError::WantRead) => self.stream.readable().await?,
-                Err(SslError::WantWrite) => self.stream.writable().await?,
-                other => return other,
-            }
+    /// Same as `poll_flush_out`, but remembers the waiting writer
+    fn poll_flush_writer(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let ret = self.as_mut().poll_flush_out(cx);
+        if ret.is_pending() {
+            *self.project().write_waker = Some(cx.waker().clone());
         }
+        ret
     }
-}
 
-impl AsyncRead for AsyncSsl {
-    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+    /// Reads encrypted data from the stream into OpenSSL
+    fn poll_fill_in(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
         let me = self.project();
+        let mut buf = [0u8; 16 * 1024];
+        let mut buf = ReadBuf::new(&mut buf);
+        ready!(me.stream.poll_read(cx, &mut buf))?;
+        // Empty read is EOF, OpenSSL will report it from the operation
+        me.ssl.feed_input(buf.filled())?;
+        Poll::Ready(Ok(()))
+    }
+
+    /// Drives an SSL operation, moving data between OpenSSL and the stream until it completes
+    fn poll_ssl<T>(mut self: Pin<&mut Self>, cx: &mut Context<'_>, mut op: impl FnMut(&mut Ssl) -> Result<T, SslError>) -> Poll<Result<T, SslError>> {
         loop {
-            let ret = me.ssl.ssl_read(buf.initialize_unfilled());
-            match ret {
-                Ok(n) => { buf.advance(n); return Poll::Ready(Ok(())) },
-                Err(SslError::WantRead) => ready!(me.stream.poll_read_ready(cx))?,
-                Err(SslError::WantWrite) => ready!(me.stream.poll_write_ready(cx))?,
-                Err(other) => return Poll::Ready(Err(other.into())),
-            };
+            match op(self.as_mut().project().ssl) {
+                Err(SslError::WantRead) => {
+                    // The peer may be waiting for our records before answering
+                    let _ = self.as_mut().poll_flush_out(cx)?;
+                    ready!(self.as_mut().poll_fill_in(cx))?;
+                }
+                // Memory buffers never block on writing
+                Err(SslError::WantWrite) => ready!(self.as_mut().poll_flush_writer(cx))?,
+                ret => {
+                    // Try to send the last records (or alert) right away,
+                    // otherwise they are sent by the next write or flush
+                    let _ = self.as_mut().poll_flush_out(cx);
+                    return Poll::Ready(ret);
+                }
+            }
         }
     }
 }
 
-impl AsyncWrite for AsyncSsl {
-    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
-        let me = self.project();
-        loop {
-            let ret = me.ssl.ssl_write(buf);
-            match ret {
-                Ok(n) => return Poll::Ready(Ok(n)),
-                Err(SslError::WantRead) => ready!(me.stream.poll_read_ready(cx))?,
-                Err(SslError::WantWrite) => ready!(me.stream.poll_write_ready(cx))?,
-                Err(other) => return Poll::Ready(Err(other.into())),
-            };
-        }
+impl<S: AsyncRead + AsyncWrite + Unpin> AsyncSsl<S> {
+    /// Performs the connection as a client
+    pub async fn connect(&mut self) -> Result<(), SslError> {
+        let handshake = self.drive(Ssl::connect_step);
+        #[cfg(feature = "tracing")]
+        let handshake = tracing::Instrument::instrument(handshake, tracing::debug_span!("tls_connect"));
+        handshake.await
+    }
+
+    /// Accepts the connection as a server
+    pub async fn accept(&mut self) -> Result<(), SslError> {
+        let handshake = self.drive(Ssl::accept_step);
+        #[cfg(feature = "tracing")]
+        let handshake = tracing::Instrument::instrument(handshake, tracing::debug_span!("tls_accept"));
+        handshake.await
     }
 
-    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+    /// Sends `close_notify` and waits for the peer's one, check [`Ssl::shutdown_bidirectional`]
+    ///
+    /// Unlike [`AsyncWrite::poll_shutdown`], leaves the stream open
+    pub async fn shutdown_bidirectional(&mut self) -> Result<(), SslError> {
+        let shutdown = self.drive(Ssl::shutdown_bidirectional_step);
+        #[cfg(feature = "tracing")]
+        let shutdown = tracing::Instrument::instrument(shutdown, tracing::debug_span!("tls_shutdown"));
+        shutdown.await
+    }
+
+    /// Runs the operation until it completes, then sends its last records
+    async fn drive(&mut self, mut op: impl FnMut(&mut Ssl) -> Result<(), SslError>) -> Result<(), SslError> {
+        poll_fn(|cx| Pin::new(&mut *self).poll_ssl(cx, &mut op)).await?;
+        poll_fn(|cx| Pin::new(&mut *self).poll_flush_writer(cx)).await?;
+        Ok(())
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite> AsyncRead for AsyncSsl<S> {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let n = ready!(self.poll_ssl(cx, |ssl| ssl.ssl_read(buf.initialize_unfilled())))?;
+        buf.advance(n);
         Poll::Ready(Ok(()))
     }
+}
 
-    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
-        let me = self.project();
-        loop {
-            let ret = me.ssl.shutdown();
-            match ret {
-                Ok(()) => return Poll::Ready(Ok(())),
-                Err(SslError::WantRead) => ready!(me.stream.poll_read_ready(cx))?,
-                Err(SslError::WantWrite) => ready!(me.stream.poll_write_ready(cx))?,
-                Err(other) => return Poll::Ready(Err(other.into())),
+impl<S: AsyncRead + AsyncWrite> AsyncWrite for AsyncSsl<S> {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        // Previous records go out first, so that buffered data stays bounded
+        ready!(self.as_mut().poll_flush_writer(cx))?;
+        let n = ready!(self.poll_ssl(cx, |ssl| ssl.ssl_write(buf)))?;
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        ready!(self.as_mut().poll_flush_writer(cx))?;
+        self.project().stream.poll_flush(cx)
+    }
+
+    /// Sends `close_notify`, then shuts down the write half of the stream
+    ///
+    /// The stream is shut down even if `close_notify` could not be sent, the error is returned afterwards
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        // Half-open and failed sessions have nothing to close, and a failed `close_notify` is not retried
+        // when the stream shutdown is pending
+        if self.shutdown_error.is_none() && self.ssl.can_send_close_notify() {
+            let ret = match ready!(self.as_mut().poll_ssl(cx, Ssl::close_notify_step)) {
+                Ok(()) => ready!(self.as_mut().poll_flush_writer(cx)),
+                Err(err) => Err(err.into()),
             };
+            *self.as_mut().project().shutdown_error = ret.err();
+        }
+        let me = self.project();
+        let ret = ready!(me.stream.poll_shutdown(cx));
+        match me.shutdown_error.take() {
+            Some(err) => Poll::Ready(Err(err)),
+            None => Poll::Ready(ret),
         }
     }
 }
@@ -0,0 +1,56 @@
+use core::ffi::{c_char, c_int, c_void};
+use core::marker::PhantomData;
+
+use crate::sys;
+use crate::ErrorStack;
+
+/// Read-only memory BIO borrowing a byte slice
+pub(crate) struct MemBioSlice<'a>(*mut sys::BIO, PhantomData<&'a [u8]>);
+
+impl<'a> MemBioSlice<'a> {
+    pub(crate) fn new(buf: &'a [u8]) -> Result<MemBioSlice<'a>, ErrorStack> {
+        let len = c_int::try_from(buf.len()).expect("buffer too large for BIO");
+        let ptr = unsafe { sys::BIO_new_mem_buf(buf.as_ptr() as *const c_void, len) };
+        if ptr.is_null() { return Err(ErrorStack::get()); }
+        Ok(MemBioSlice(ptr, PhantomData))
+    }
+
+    pub(crate) fn as_ptr(&self) -> *mut sys::BIO {
+        self.0
+    }
+}
+
+impl Drop for MemBioSlice<'_> {
+    fn drop(&mut self) {
+        unsafe { sys::BIO_free(self.0) };
+    }
+}
+
+/// Growable memory BIO for writing into
+pub(crate) struct MemBio(*mut sys::BIO);
+
+impl MemBio {
+    pub(crate) fn new() -> Result<MemBio, ErrorStack> {
+        let ptr = unsafe { sys::BIO_new(sys::BIO_s_mem()) };
+        if ptr.is_null() { return Err(ErrorStack::get()); }
+        Ok(MemBio(ptr))
+    }
+
+    pub(crate) fn as_ptr(&self) -> *mut sys::BIO {
+        self.0
+    }
+
+    /// Returns everything written so far
+    pub(crate) fn get_buf(&self) -> &[u8] {
+        let mut ptr: *mut c_char = core::ptr::null_mut();
+        let len = unsafe { sys::BIO_get_mem_data(self.0, &mut ptr) };
+        if ptr.is_null() || len <= 0 { return &[]; }
+        unsafe { core::slice::from_raw_parts(ptr as *const u8, len as usize) }
+    }
+}
+
+impl Drop for MemBio {
+    fn drop(&mut self) {
+        unsafe { sys::BIO_free(self.0) };
+    }
+}
@@ -0,0 +1,134 @@
+use core::net::IpAddr;
+use std::ffi::CString;
+use std::io::{Read, Write};
+use std::sync::Arc;
+
+#[cfg(feature = "tokio")]
+use tokio::io::{AsyncRead, AsyncWrite};
+
+use crate::{SslCtx, Ssl, SslStream, ErrorStack, SslError, VerifyMode};
+#[cfg(feature = "tokio")]
+use crate::AsyncSsl;
+
+/// Client side of TLS connections with safe defaults, cheap to clone
+///
+/// Example usage:
+/// ```
+/// # fn main() -> std::io::Result<()> {
+/// # use openssl_lite::TlsConnector;
+/// let connector = TlsConnector::new()?;
+/// # /*
+/// let socket = TcpStream::connect("Neltharion01.github.io:443")?;
+/// let mut stream = connector.connect("Neltharion01.github.io", socket)?;
+/// # */
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct TlsConnector(Arc<SslCtx>);
+
+impl TlsConnector {
+    /// Constructs a connector that verifies peers against the system trust store
+    pub fn new() -> Result<TlsConnector, ErrorStack> {
+        let mut ctx = SslCtx::new()?;
+        ctx.set_default_verify_paths()?;
+        Ok(TlsConnector(Arc::new(ctx)))
+    }
+
+    /// Constructs a connector from a custom context
+    pub fn from_ctx(ctx: SslCtx) -> TlsConnector {
+        TlsConnector(Arc::new(ctx))
+    }
+
+    /// Returns the shared context
+    pub fn ctx(&self) -> &SslCtx {
+        &self.0
+    }
+
+    /// Creates an SSL object for connecting to `domain`
+    ///
+    /// IP addresses (IPv6 optionally in brackets) are verified against the IP of the certificate,
+    /// and are not sent via SNI
+    pub fn configure(&self, domain: &str) -> Result<Ssl, ErrorStack> {
+        let mut ssl = Ssl::new(&self.0)?;
+        let Ok(name) = CString::new(domain) else {
+            return Err(ErrorStack::custom("domain contains a NUL byte"));
+        };
+
+        let ip = domain.strip_prefix('[').and_then(|d| d.strip_suffix(']')).unwrap_or(domain);
+        match ip.parse::<IpAddr>() {
+            Ok(ip) => ssl.set_verify_ip(ip)?,
+            Err(_) => ssl.set_hostname(&name)?,
+        }
+        Ok(ssl)
+    }
+
+    /// Connects to `domain` over the stream
+    pub fn connect<S: Read + Write + 'static>(&self, domain: &str, stream: S) -> Result<SslStream<S>, SslError> {
+        let mut stream = SslStream::new(self.configure(domain)?, stream)?;
+        stream.connect()?;
+        Ok(stream)
+    }
+
+    /// Async version of [`TlsConnector::connect`]
+    #[cfg(feature = "tokio")]
+    pub async fn connect_async<S: AsyncRead + AsyncWrite + Unpin>(&self, domain: &str, stream: S) -> Result<AsyncSsl<S>, SslError> {
+        let mut stream = AsyncSsl::from_ssl(self.configure(domain)?, stream)?;
+        stream.connect().await?;
+        Ok(stream)
+    }
+}
+
+/// Server side of TLS connections, cheap to clone
+///
+/// Example usage:
+/// ```
+/// # fn main() -> std::io::Result<()> {
+/// # use openssl_lite::TlsAcceptor;
+/// # /*
+/// let acceptor = TlsAcceptor::from_pem(&std::fs::read("chain.pem")?, &std::fs::read("key.pem")?)?;
+/// let (socket, _) = listener.accept()?;
+/// let mut stream = acceptor.accept(socket)?;
+/// # */
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct TlsAcceptor(Arc<SslCtx>);
+
+impl TlsAcceptor {
+    /// Constructs an acceptor presenting the PEM certificate chain (leaf first) and key
+    ///
+    /// Client certificates are not requested
+    pub fn from_pem(chain: &[u8], key: &[u8]) -> Result<TlsAcceptor, ErrorStack> {
+        let mut ctx = SslCtx::new()?;
+        ctx.set_verify_mode(VerifyMode::NONE);
+        ctx.load_certificate_chain_from_pem(chain, key)?;
+        Ok(TlsAcceptor(Arc::new(ctx)))
+    }
+
+    /// Constructs an acceptor from a custom context, which must have a certificate and key
+    pub fn from_ctx(ctx: SslCtx) -> TlsAcceptor {
+        TlsAcceptor(Arc::new(ctx))
+    }
+
+    /// Returns the shared context
+    pub fn ctx(&self) -> &SslCtx {
+        &self.0
+    }
+
+    /// Accepts a TLS connection over the stream
+    pub fn accept<S: Read + Write + 'static>(&self, stream: S) -> Result<SslStream<S>, SslError> {
+        let mut stream = SslStream::new(Ssl::new(&self.0)?, stream)?;
+        stream.accept()?;
+        Ok(stream)
+    }
+
+    /// Async version of [`TlsAcceptor::accept`]
+    #[cfg(feature = "tokio")]
+    pub async fn accept_async<S: AsyncRead + AsyncWrite + Unpin>(&self, stream: S) -> Result<AsyncSsl<S>, SslError> {
+        let mut stream = AsyncSsl::new(&self.0, stream)?;
+        stream.accept().await?;
+        Ok(stream)
+    }
+}
@@ -1,7 +1,91 @@
-use core::ffi::{CStr, c_long};
+use core::ffi::{CStr, c_char, c_int, c_long, c_uint, c_void};
+use core::time::Duration;
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
 
-use crate::sys;
-use crate::ErrorStack;
+use crate::{sys, ex_data};
+use crate::{ErrorStack, X509, X509Ref, X509StoreContextRef, X509VerifyError, VerifyError, PKey};
+use crate::session::{ClientSessionCache, new_session_trampoline, TicketKeysHolder, ticket_key_trampoline};
+use crate::{SessionCacheMode, TicketKeys, TlsVersion};
+use crate::sni::{ServernameCallback, servername_trampoline, original_ctx};
+use crate::SniRequest;
+use crate::trace::{InfoCallback, MsgCallback, info_trampoline, msg_trampoline};
+use crate::ssl::catch_callback;
+use crate::{InfoEvent, Message};
+
+type VerifyFn = dyn Fn(bool, &X509StoreContextRef) -> bool + Send + Sync;
+pub(crate) struct VerifyCallback(Box<VerifyFn>);
+
+type AlpnSelectFn = dyn for<'a> Fn(&[&'a [u8]]) -> Option<&'a [u8]> + Send + Sync;
+struct AlpnSelectCallback(Box<AlpnSelectFn>);
+
+type KeylogFn = dyn Fn(&str) + Send + Sync;
+struct KeylogCallback(Box<KeylogFn>);
+
+/// Encoding of certificate and key files
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileType {
+    /// Base64 with `-----BEGIN ...-----` headers
+    Pem,
+    /// Binary ASN.1
+    Der,
+}
+
+impl FileType {
+    fn raw(self) -> c_int {
+        match self {
+            FileType::Pem => sys::SSL_FILETYPE_PEM,
+            FileType::Der => sys::SSL_FILETYPE_ASN1,
+        }
+    }
+}
+
+/// Peer certificate verification flags, combined with `|`
+///
+/// Example: `VerifyMode::PEER | VerifyMode::FAIL_IF_NO_PEER_CERT` for a server that requires client certificates
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VerifyMode(c_int);
+
+impl VerifyMode {
+    /// Client: do not verify the server. Server: do not request a client certificate
+    pub const NONE: VerifyMode = VerifyMode(sys::SSL_VERIFY_NONE);
+    /// Client: verify the server. Server: request a client certificate and verify it if sent
+    pub const PEER: VerifyMode = VerifyMode(sys::SSL_VERIFY_PEER);
+    /// Server: fail the handshake if the client did not send a certificate. Requires `PEER`
+    pub const FAIL_IF_NO_PEER_CERT: VerifyMode = VerifyMode(sys::SSL_VERIFY_FAIL_IF_NO_PEER_CERT);
+    /// Server: only request a client certificate once, not on renegotiation. Requires `PEER`
+    pub const CLIENT_ONCE: VerifyMode = VerifyMode(sys::SSL_VERIFY_CLIENT_ONCE);
+    /// Server: only request a client certificate after the TLS 1.3 handshake. Requires `PEER`
+    pub const POST_HANDSHAKE: VerifyMode = VerifyMode(sys::SSL_VERIFY_POST_HANDSHAKE);
+
+    /// Returns true if all flags from `other` are set
+    pub fn contains(self, other: VerifyMode) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub(crate) fn from_bits(bits: c_int) -> VerifyMode {
+        VerifyMode(bits)
+    }
+
+    pub(crate) fn bits(self) -> c_int {
+        self.0
+    }
+}
+
+impl core::ops::BitOr for VerifyMode {
+    type Output = VerifyMode;
+    fn bitor(self, rhs: VerifyMode) -> VerifyMode {
+        VerifyMode(self.0 | rhs.0)
+    }
+}
+
+impl core::ops::BitOrAssign for VerifyMode {
+    fn bitor_assign(&mut self, rhs: VerifyMode) {
+        self.0 |= rhs.0;
+    }
+}
 
 /// SSL context
 pub struct SslCtx(pub(crate) *mut sys::SSL_CTX);
@@ -12,47 +96,175 @@ unsafe impl Sync for SslCtx {}
 impl SslCtx {
     /// Construct a new SSL context
     ///
-    /// By default, verifies certificates and only accepts TLSv1.2 and newer
+    /// By default, verifies certificates and only accepts TLSv1.2 and newer.
+    /// Servers that verify client certificates also need [`SslCtx::set_session_id_context`] to resume sessions
     #[doc(alias = "SSL_CTX_new")]
     pub fn new() -> Result<SslCtx, ErrorStack> {
+        ErrorStack::clear();
         let ptr = unsafe { sys::SSL_CTX_new(sys::TLS_method()) };
         if ptr.is_null() { return Err(ErrorStack::get()); }
 
+        // Setters may run while connections of the context are in callbacks on other threads
+        unsafe {
+            ex_data::ctx_reserve::<VerifyCallback>(ptr);
+            ex_data::ctx_reserve::<ClientSessionCache>(ptr);
+            ex_data::ctx_reserve::<TicketKeysHolder>(ptr);
+            ex_data::ctx_reserve::<AlpnSelectCallback>(ptr);
+            ex_data::ctx_reserve::<ServernameCallback>(ptr);
+            ex_data::ctx_reserve::<KeylogCallback>(ptr);
+            ex_data::ctx_reserve::<InfoCallback>(ptr);
+            ex_data::ctx_reserve::<MsgCallback>(ptr);
+        }
+
         let mut ctx = SslCtx(ptr);
-        ctx.set_min_version(crate::version::TLS1_2_VERSION)?;
-        ctx.set_verify(true);
+        ctx.set_min_version(Some(TlsVersion::Tls1_2))?;
+        // Resets verification failures of earlier handshakes, and traces handshake events even without a user callback
+        unsafe { sys::SSL_CTX_set_info_callback(ptr, Some(info_trampoline)) };
+        // Records verification failures even without a user callback
+        unsafe { sys::SSL_CTX_set_verify(ptr, sys::SSL_VERIFY_PEER, Some(ctx_verify_trampoline)) };
 
         Ok(ctx)
     }
 
     /// Sets default verify paths
     ///
-    /// You always need this for client
+    /// You always need this for client, unless you load your own trust roots
     #[doc(alias = "SSL_CTX_set_default_verify_paths")]
     pub fn set_default_verify_paths(&mut self) -> Result<(), ErrorStack> {
+        ErrorStack::clear();
         let ret = unsafe { sys::SSL_CTX_set_default_verify_paths(self.0) };
         if ret == 0 { return Err(ErrorStack::get()); }
         /* success == 1 */ Ok(())
     }
 
-    /// Set min TLS version. Accepts constants from [`crate::version`]
+    /// Trusts CA certificates from the PEM file, in addition to already loaded ones
+    #[doc(alias = "SSL_CTX_load_verify_file", alias = "SSL_CTX_load_verify_locations")]
+    pub fn load_verify_file(&mut self, file: &CStr) -> Result<(), ErrorStack> {
+        ErrorStack::clear();
+        let ret = unsafe { sys::SSL_CTX_load_verify_file(self.0, file.as_ptr()) };
+        if ret == 0 { return Err(ErrorStack::get()); }
+        /* success == 1 */ Ok(())
+    }
+
+    /// Trusts CA certificates from the hashed directory (see `openssl rehash`), in addition to already loaded ones
+    #[doc(alias = "SSL_CTX_load_verify_dir", alias = "SSL_CTX_load_verify_locations")]
+    pub fn load_verify_dir(&mut self, dir: &CStr) -> Result<(), ErrorStack> {
+        ErrorStack::clear();
+        let ret = unsafe { sys::SSL_CTX_load_verify_dir(self.0, dir.as_ptr()) };
+        if ret == 0 { return Err(ErrorStack::get()); }
+        /* success == 1 */ Ok(())
+    }
+
+    /// Trusts the given CA certificate, in addition to already loaded ones
+    #[doc(alias = "X509_STORE_add_cert")]
+    pub fn add_trusted_certificate(&mut self, cert: &X509Ref) -> Result<(), ErrorStack> {
+        ErrorStack::clear();
+        let store = unsafe { sys::SSL_CTX_get_cert_store(self.0) };
+        let ret = unsafe { sys::X509_STORE_add_cert(store, cert.as_ptr()) };
+        if ret == 0 { return Err(ErrorStack::get()); }
+        /* success == 1 */ Ok(())
+    }
+
+    /// Replaces the trust store with an empty one, forgetting all trusted certificates
+    ///
+    /// Call this before loading your own roots to stop trusting the system store
+    #[doc(alias = "SSL_CTX_set_cert_store", alias = "X509_STORE_new")]
+    pub fn clear_trusted_certificates(&mut self) -> Result<(), ErrorStack> {
+        ErrorStack::clear();
+        let store = unsafe { sys::X509_STORE_new() };
+        if store.is_null() { return Err(ErrorStack::get()); }
+        // Takes ownership of the new store and frees the old one
+        unsafe { sys::SSL_CTX_set_cert_store(self.0, store) };
+        Ok(())
+    }
+
+    /// Sets the minimal protocol version, `None` enables all versions supported by OpenSSL
     ///
     /// By default, it is TLS 1.2
     #[doc(alias = "SSL_CTX_set_min_proto_version")]
-    pub fn set_min_version(&mut self, ver: c_long) -> Result<(), ErrorStack> {
-        let ret = unsafe { sys::SSL_CTX_set_min_proto_version(self.0, ver) };
+    pub fn set_min_version(&mut self, ver: Option<TlsVersion>) -> Result<(), ErrorStack> {
+        ErrorStack::clear();
+        let ret = unsafe { sys::SSL_CTX_set_min_proto_version(self.0, ver.map_or(0, TlsVersion::as_raw)) };
+        if ret == 0 { return Err(ErrorStack::get()); }
+        /* success == 1 */ Ok(())
+    }
+
+    /// Sets the maximal protocol version, `None` enables all versions supported by OpenSSL
+    ///
+    /// Example: `ctx.set_max_version(Some(TlsVersion::Tls1_2))` to talk to peers with broken TLS 1.3
+    #[doc(alias = "SSL_CTX_set_max_proto_version")]
+    pub fn set_max_version(&mut self, ver: Option<TlsVersion>) -> Result<(), ErrorStack> {
+        ErrorStack::clear();
+        let ret = unsafe { sys::SSL_CTX_set_max_proto_version(self.0, ver.map_or(0, TlsVersion::as_raw)) };
         if ret == 0 { return Err(ErrorStack::get()); }
         /* success == 1 */ Ok(())
     }
 
+    /// Returns the minimal protocol version, or `None` if there is no bound
+    #[doc(alias = "SSL_CTX_get_min_proto_version")]
+    pub fn min_version(&self) -> Option<TlsVersion> {
+        TlsVersion::from_raw(unsafe { sys::SSL_CTX_get_min_proto_version(self.0) })
+    }
+
+    /// Returns the maximal protocol version, or `None` if there is no bound
+    #[doc(alias = "SSL_CTX_get_max_proto_version")]
+    pub fn max_version(&self) -> Option<TlsVersion> {
+        TlsVersion::from_raw(unsafe { sys::SSL_CTX_get_max_proto_version(self.0) })
+    }
+
     /// Set supported cipher list
+    ///
+    /// Only affects TLS 1.2 and below, check [`SslCtx::set_ciphersuites`] for TLS 1.3
     #[doc(alias = "SSL_CTX_set_cipher_list")]
     pub fn set_cipher_list(&mut self, list: &CStr) -> Result<(), ErrorStack> {
+        ErrorStack::clear();
         let ret = unsafe { sys::SSL_CTX_set_cipher_list(self.0, list.as_ptr()) };
         if ret == 0 { return Err(ErrorStack::get()); }
         /* success == 1 */ Ok(())
     }
 
+    /// Sets TLS 1.3 cipher suites, in order of preference
+    ///
+    /// Example: `ctx.set_ciphersuites(c"TLS_AES_256_GCM_SHA384:TLS_CHACHA20_POLY1305_SHA256")`
+    #[doc(alias = "SSL_CTX_set_ciphersuites")]
+    pub fn set_ciphersuites(&mut self, list: &CStr) -> Result<(), ErrorStack> {
+        ErrorStack::clear();
+        let ret = unsafe { sys::SSL_CTX_set_ciphersuites(self.0, list.as_ptr()) };
+        if ret == 0 { return Err(ErrorStack::get()); }
+        /* success == 1 */ Ok(())
+    }
+
+    /// Sets supported key exchange groups, in order of preference
+    ///
+    /// Example: `ctx.set_groups_list(c"X25519MLKEM768:X25519:P-256")`
+    #[doc(alias = "SSL_CTX_set1_groups_list")]
+    pub fn set_groups_list(&mut self, list: &CStr) -> Result<(), ErrorStack> {
+        ErrorStack::clear();
+        let ret = unsafe { sys::SSL_CTX_set1_groups_list(self.0, list.as_ptr()) };
+        if ret == 0 { return Err(ErrorStack::get()); }
+        /* success == 1 */ Ok(())
+    }
+
+    /// Sets signature algorithms supported for the handshake, in order of preference
+    ///
+    /// Example: `ctx.set_sigalgs_list(c"ECDSA+SHA256:rsa_pss_rsae_sha256:ed25519")`
+    #[doc(alias = "SSL_CTX_set1_sigalgs_list")]
+    pub fn set_sigalgs_list(&mut self, list: &CStr) -> Result<(), ErrorStack> {
+        ErrorStack::clear();
+        let ret = unsafe { sys::SSL_CTX_set1_sigalgs_list(self.0, list.as_ptr()) };
+        if ret == 0 { return Err(ErrorStack::get()); }
+        /* success == 1 */ Ok(())
+    }
+
+    /// Sets signature algorithms accepted for client certificates, if different from [`SslCtx::set_sigalgs_list`]
+    #[doc(alias = "SSL_CTX_set1_client_sigalgs_list")]
+    pub fn set_client_sigalgs_list(&mut self, list: &CStr) -> Result<(), ErrorStack> {
+        ErrorStack::clear();
+        let ret = unsafe { sys::SSL_CTX_set1_client_sigalgs_list(self.0, list.as_ptr()) };
+        if ret == 0 { return Err(ErrorStack::get()); }
+        /* success == 1 */ Ok(())
+    }
+
     /// Sets SSL options. Check `SSL_CTX_set_options` manpage for available options.
     ///
     /// Accepts constants from [`crate::op`]
@@ -64,26 +276,522 @@ impl SslCtx {
     }
 
     /// Enable/disable certificate verification
+    ///
+    /// Same as [`SslCtx::set_verify_mode`] with `PEER` or `NONE`
     #[doc(alias = "SSL_CTX_set_verify")]
     pub fn set_verify(&mut self, verify: bool) {
-        let mode = if verify { sys::SSL_VERIFY_PEER } else { sys::SSL_VERIFY_NONE };
-        unsafe { sys::SSL_CTX_set_verify(self.0, mode, core::ptr::null()) };
+        self.set_verify_mode(if verify { VerifyMode::PEER } else { VerifyMode::NONE });
     }
 
-    /// Load server's certificate and private key files
-    #[doc(alias = "SSL_CTX_use_certificate_file", alias = "SSL_CTX_use_PrivateKey_file", alias = "SSL_CTX_check_private_key")]
+    /// Sets certificate verification flags
+    #[doc(alias = "SSL_CTX_set_verify")]
+    pub fn set_verify_mode(&mut self, mode: VerifyMode) {
+        unsafe {
+            let callback = sys::SSL_CTX_get_verify_callback(self.0);
+            sys::SSL_CTX_set_verify(self.0, mode.0, callback);
+        }
+    }
+
+    /// Sets a callback that is called for every certificate in the peer chain
+    ///
+    /// Receives the result of OpenSSL's own verification of the current certificate, and returns the final result.
+    /// Returning `false` aborts the handshake. Verification flags are not changed,
+    /// and the callback is only called if they include `PEER`.
+    ///
+    /// Example (logging verification failures):
+    /// ```
+    /// # fn main() -> std::io::Result<()> {
+    /// # use openssl_lite::SslCtx;
+    /// let mut ctx = SslCtx::new()?;
+    /// ctx.set_verify_callback(|ok, store| {
+    ///     if !ok { eprintln!("verify error {} at depth {}", store.error_code(), store.error_depth()); }
+    ///     ok
+    /// });
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[doc(alias = "SSL_CTX_set_verify")]
+    pub fn set_verify_callback<F>(&mut self, callback: F)
+    where F: Fn(bool, &X509StoreContextRef) -> bool + Send + Sync + 'static {
+        unsafe {
+            ex_data::ctx_set(self.0, VerifyCallback(Box::new(callback)));
+            sys::SSL_CTX_set_verify(self.0, sys::SSL_CTX_get_verify_mode(self.0), Some(ctx_verify_trampoline));
+        }
+    }
+
+    /// Returns current certificate verification flags
+    #[doc(alias = "SSL_CTX_get_verify_mode")]
+    pub fn verify_mode(&self) -> VerifyMode {
+        VerifyMode(unsafe { sys::SSL_CTX_get_verify_mode(self.0) })
+    }
+
+    /// Sets the list of CAs sent to the client when requesting its certificate
+    ///
+    /// Only subject names are sent; to actually trust them, use [`SslCtx::add_trusted_certificate`]
+    #[doc(alias = "SSL_CTX_set_client_CA_list")]
+    pub fn set_client_ca_list(&mut self, cas: &[X509]) -> Result<(), ErrorStack> {
+        ErrorStack::clear();
+        let list = unsafe { sys::OPENSSL_sk_new_null() };
+        if list.is_null() { return Err(ErrorStack::get()); }
+
+        for ca in cas {
+            let name = unsafe { sys::X509_NAME_dup(sys::X509_get_subject_name(ca.0)) };
+            if name.is_null() || unsafe { sys::OPENSSL_sk_push(list, name as *const c_void) } == 0 {
+                let err = ErrorStack::get();
+                unsafe {
+                    sys::X509_NAME_free(name);
+                    sys::sk_X509_NAME_pop_free(list);
+                }
+                return Err(err);
+            }
+        }
+
+        // Takes ownership of the list
+        unsafe { sys::SSL_CTX_set_client_CA_list(self.0, list) };
+        Ok(())
+    }
+
+    /// Sets the list of CAs sent to the client from subjects of certificates in the PEM file
+    #[doc(alias = "SSL_load_client_CA_file", alias = "SSL_CTX_set_client_CA_list")]
+    pub fn load_client_ca_file(&mut self, file: &CStr) -> Result<(), ErrorStack> {
+        ErrorStack::clear();
+        let list = unsafe { sys::SSL_load_client_CA_file(file.as_ptr()) };
+        if list.is_null() { return Err(ErrorStack::get()); }
+        unsafe { sys::SSL_CTX_set_client_CA_list(self.0, list) };
+        Ok(())
+    }
+
+    /// Load server's certificate chain and private key PEM files
+    ///
+    /// On a client, this is the certificate presented when the server requests one
+    #[doc(alias = "SSL_CTX_use_certificate_chain_file", alias = "SSL_CTX_use_PrivateKey_file", alias = "SSL_CTX_check_private_key")]
     pub fn load_certificate_chain(&mut self, certificate: &CStr, key: &CStr) -> Result<(), ErrorStack> {
-        let ret = unsafe { sys::SSL_CTX_use_certificate_file(self.0, certificate.as_ptr(), sys::SSL_FILETYPE_PEM) };
+        self.load_certificate_chain_with_type(certificate, key, FileType::Pem)
+    }
+
+    /// Load server's certificate and private key files of the given type
+    ///
+    /// PEM certificate file may contain the whole chain, DER file only contains the leaf certificate
+    #[doc(alias = "SSL_CTX_use_certificate_file", alias = "SSL_CTX_use_PrivateKey_file", alias = "SSL_CTX_check_private_key")]
+    pub fn load_certificate_chain_with_type(&mut self, certificate: &CStr, key: &CStr, filetype: FileType) -> Result<(), ErrorStack> {
+        ErrorStack::clear();
+        let ret = match filetype {
+            FileType::Pem => unsafe { sys::SSL_CTX_use_certificate_chain_file(self.0, certificate.as_ptr()) },
+            FileType::Der => {
+                // Unlike the PEM chain, a single certificate keeps the intermediates loaded before
+                self.clear_chain_certificates();
+                unsafe { sys::SSL_CTX_use_certificate_file(self.0, certificate.as_ptr(), sys::SSL_FILETYPE_ASN1) }
+            }
+        };
+        if ret == 0 { return Err(ErrorStack::get()); }
+
+        let ret = unsafe { sys::SSL_CTX_use_PrivateKey_file(self.0, key.as_ptr(), filetype.raw()) };
+        if ret == 0 { return Err(ErrorStack::get()); }
+
+        self.check_private_key()
+    }
+
+    /// Load certificate chain (leaf first, then intermediates) and private key from PEM buffers
+    pub fn load_certificate_chain_from_pem(&mut self, chain: &[u8], key: &[u8]) -> Result<(), ErrorStack> {
+        let certs = X509::stack_from_pem(chain)?;
+        let key = PKey::private_key_from_pem(key)?;
+        self.set_certificate_chain(&certs, &key)
+    }
+
+    /// Load certificate chain (leaf first, then intermediates) and private key from DER buffers
+    pub fn load_certificate_chain_from_der(&mut self, chain: &[&[u8]], key: &[u8]) -> Result<(), ErrorStack> {
+        let certs = chain.iter().map(|der| X509::from_der(der)).collect::<Result<Vec<_>, _>>()?;
+        let key = PKey::private_key_from_der(key)?;
+        self.set_certificate_chain(&certs, &key)
+    }
+
+    fn set_certificate_chain(&mut self, certs: &[X509], key: &PKey) -> Result<(), ErrorStack> {
+        let Some((leaf, intermediates)) = certs.split_first() else {
+            return Err(ErrorStack::custom("empty certificate chain"));
+        };
+        self.use_certificate(leaf)?;
+        // Reloading (e.g. on rotation) replaces the chain instead of appending to it
+        self.clear_chain_certificates();
+        for cert in intermediates {
+            self.add_chain_certificate(cert)?;
+        }
+        self.use_private_key(key)?;
+        self.check_private_key()
+    }
+
+    /// Sets the leaf certificate
+    #[doc(alias = "SSL_CTX_use_certificate")]
+    pub fn use_certificate(&mut self, cert: &X509Ref) -> Result<(), ErrorStack> {
+        ErrorStack::clear();
+        let ret = unsafe { sys::SSL_CTX_use_certificate(self.0, cert.as_ptr()) };
         if ret == 0 { return Err(ErrorStack::get()); }
+        /* success == 1 */ Ok(())
+    }
 
-        let ret = unsafe { sys::SSL_CTX_use_PrivateKey_file(self.0, key.as_ptr(), sys::SSL_FILETYPE_PEM) };
+    /// Appends an intermediate certificate to the chain sent to the peer
+    #[doc(alias = "SSL_CTX_add1_chain_cert")]
+    pub fn add_chain_certificate(&mut self, cert: &X509Ref) -> Result<(), ErrorStack> {
+        ErrorStack::clear();
+        let ret = unsafe { sys::SSL_CTX_add1_chain_cert(self.0, cert.as_ptr()) };
         if ret == 0 { return Err(ErrorStack::get()); }
+        /* success == 1 */ Ok(())
+    }
 
+    /// Removes all intermediate certificates from the chain sent to the peer
+    #[doc(alias = "SSL_CTX_clear_chain_certs")]
+    pub fn clear_chain_certificates(&mut self) {
+        unsafe { sys::SSL_CTX_clear_chain_certs(self.0) };
+    }
+
+    /// Sets the private key
+    #[doc(alias = "SSL_CTX_use_PrivateKey")]
+    pub fn use_private_key(&mut self, key: &PKey) -> Result<(), ErrorStack> {
+        ErrorStack::clear();
+        let ret = unsafe { sys::SSL_CTX_use_PrivateKey(self.0, key.0) };
+        if ret == 0 { return Err(ErrorStack::get()); }
+        /* success == 1 */ Ok(())
+    }
+
+    /// Checks that the private key matches the certificate
+    #[doc(alias = "SSL_CTX_check_private_key")]
+    pub fn check_private_key(&self) -> Result<(), ErrorStack> {
+        ErrorStack::clear();
         let ret = unsafe { sys::SSL_CTX_check_private_key(self.0) };
         if ret == 0 { return Err(ErrorStack::get()); }
+        /* success == 1 */ Ok(())
+    }
+
+    /// Enables client-side session resumption
+    ///
+    /// Sessions are cached per host set with [`Ssl::set_hostname`](crate::Ssl::set_hostname),
+    /// and are automatically offered when connecting to the same host again.
+    /// At most `capacity` hosts are remembered. Calling it again replaces the cache with an empty one,
+    /// sessions of running connections are stored into the new cache.
+    ///
+    /// Replaces the session cache mode, so only use it on client contexts
+    #[doc(alias = "SSL_CTX_sess_set_new_cb", alias = "SSL_CTX_set_session_cache_mode")]
+    pub fn enable_client_session_cache(&mut self, capacity: usize) {
+        unsafe {
+            ex_data::ctx_set(self.0, ClientSessionCache::new(capacity));
+            sys::SSL_CTX_set_session_cache_mode(self.0, sys::SSL_SESS_CACHE_CLIENT | sys::SSL_SESS_CACHE_NO_INTERNAL_STORE);
+            sys::SSL_CTX_sess_set_new_cb(self.0, Some(new_session_trampoline));
+        }
+    }
+
+    /// Sets session cache flags
+    ///
+    /// By default, servers cache sessions and clients do not
+    #[doc(alias = "SSL_CTX_set_session_cache_mode")]
+    pub fn set_session_cache_mode(&mut self, mode: SessionCacheMode) {
+        unsafe { sys::SSL_CTX_set_session_cache_mode(self.0, mode.bits()) };
+    }
+
+    /// Returns current session cache flags
+    #[doc(alias = "SSL_CTX_get_session_cache_mode")]
+    pub fn session_cache_mode(&self) -> SessionCacheMode {
+        SessionCacheMode::from_bits(unsafe { sys::SSL_CTX_get_session_cache_mode(self.0) })
+    }
+
+    /// Sets the maximal number of sessions in the internal server cache, 0 means unlimited
+    ///
+    /// By default, it is 20480
+    #[doc(alias = "SSL_CTX_sess_set_cache_size")]
+    pub fn set_session_cache_size(&mut self, size: usize) {
+        let size = c_long::try_from(size).unwrap_or(c_long::MAX);
+        unsafe { sys::SSL_CTX_sess_set_cache_size(self.0, size) };
+    }
+
+    /// Sets the lifetime of new sessions and tickets
+    ///
+    /// By default, it is 2 hours
+    #[doc(alias = "SSL_CTX_set_timeout")]
+    pub fn set_session_timeout(&mut self, timeout: Duration) {
+        let secs = c_long::try_from(timeout.as_secs()).unwrap_or(c_long::MAX);
+        unsafe { sys::SSL_CTX_set_timeout(self.0, secs) };
+    }
+
+    /// Sets the number of TLS 1.3 tickets the server sends after a full handshake
+    ///
+    /// By default, it is 2. Set to 0 to disable TLS 1.3 resumption
+    #[doc(alias = "SSL_CTX_set_num_tickets")]
+    pub fn set_num_tickets(&mut self, num: usize) -> Result<(), ErrorStack> {
+        ErrorStack::clear();
+        let ret = unsafe { sys::SSL_CTX_set_num_tickets(self.0, num) };
+        if ret == 0 { return Err(ErrorStack::get()); }
+        /* success == 1 */ Ok(())
+    }
+
+    /// Sets the session ID context, up to 32 bytes
+    ///
+    /// Sessions are only resumed on contexts with the same ID context.
+    /// Set distinct values for contexts with different verification settings that share ticket keys.
+    ///
+    /// There is no default. Servers that verify client certificates ([`VerifyMode::PEER`]) and resume sessions
+    /// must set it, otherwise OpenSSL fails the handshakes of clients offering a session
+    #[doc(alias = "SSL_CTX_set_session_id_context")]
+    pub fn set_session_id_context(&mut self, sid_ctx: &[u8]) -> Result<(), ErrorStack> {
+        ErrorStack::clear();
+        if sid_ctx.len() > sys::SSL_MAX_SID_CTX_LENGTH {
+            return Err(ErrorStack::custom(format!("session ID context longer than {} bytes", sys::SSL_MAX_SID_CTX_LENGTH)));
+        }
+        let ret = unsafe { sys::SSL_CTX_set_session_id_context(self.0, sid_ctx.as_ptr(), sid_ctx.len() as c_uint) };
+        if ret == 0 { return Err(ErrorStack::get()); }
+        /* success == 1 */ Ok(())
+    }
 
+    /// Encrypts session tickets with the given keys instead of random per-context ones
+    ///
+    /// Servers sharing the keys can resume each other's sessions. Keys can be rotated with [`TicketKeys::rotate`]
+    /// while the context is in use:
+    /// ```
+    /// # fn main() -> std::io::Result<()> {
+    /// # use std::sync::Arc;
+    /// # use openssl_lite::{SslCtx, TicketKey, TicketKeys};
+    /// let keys = Arc::new(TicketKeys::new(TicketKey::generate()?, 3));
+    /// let mut ctx = SslCtx::new()?;
+    /// ctx.set_ticket_keys(keys.clone())?;
+    ///
+    /// // Later, e.g. every hour
+    /// keys.rotate(TicketKey::generate()?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// Calling it again replaces the keys, running handshakes issue and decrypt tickets with the new ones.
+    #[doc(alias = "SSL_CTX_set_tlsext_ticket_key_evp_cb")]
+    pub fn set_ticket_keys(&mut self, keys: Arc<TicketKeys>) -> Result<(), ErrorStack> {
+        ErrorStack::clear();
+        unsafe { ex_data::ctx_set(self.0, TicketKeysHolder(keys)) };
+        let ret = unsafe { sys::SSL_CTX_set_tlsext_ticket_key_evp_cb(self.0, Some(ticket_key_trampoline)) };
+        if ret == 0 { return Err(ErrorStack::get()); }
+        /* success == 1 */ Ok(())
+    }
+
+    /// Sets the list of protocols advertised by the client via ALPN, in order of preference
+    ///
+    /// Example: `ctx.set_alpn_protocols(&[b"h2", b"http/1.1"])`
+    ///
+    /// Fails if any protocol name is empty or longer than 255 bytes
+    #[doc(alias = "SSL_CTX_set_alpn_protos")]
+    pub fn set_alpn_protocols(&mut self, protocols: &[&[u8]]) -> Result<(), ErrorStack> {
+        ErrorStack::clear();
+        let wire = alpn_wire_format(protocols)?;
+        let ret = unsafe { sys::SSL_CTX_set_alpn_protos(self.0, wire.as_ptr(), wire.len() as u32) };
+        if ret != 0 { return Err(ErrorStack::get()); }
+        /* success == 0 */ Ok(())
+    }
+
+    /// Sets the server callback that selects a protocol from the list offered by the client
+    ///
+    /// The callback must return one of the offered protocols, or `None` to not negotiate ALPN at all.
+    /// Returning another protocol fails the handshake with the `no_application_protocol` alert:
+    /// ```
+    /// # fn main() -> std::io::Result<()> {
+    /// # use openssl_lite::SslCtx;
+    /// let mut ctx = SslCtx::new()?;
+    /// ctx.set_alpn_select_callback(|offered| offered.iter().copied().find(|&p| p == b"h2"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[doc(alias = "SSL_CTX_set_alpn_select_cb")]
+    pub fn set_alpn_select_callback<F>(&mut self, callback: F)
+    where F: for<'a> Fn(&[&'a [u8]]) -> Option<&'a [u8]> + Send + Sync + 'static {
+        unsafe {
+            ex_data::ctx_set(self.0, AlpnSelectCallback(Box::new(callback)));
+            sys::SSL_CTX_set_alpn_select_cb(self.0, Some(alpn_select_trampoline), core::ptr::null_mut());
+        }
+    }
+
+    /// Sets the server callback invoked with the server name (SNI) requested by the client
+    ///
+    /// The callback may switch to another context or certificate. Returning false aborts the handshake
+    /// with the `unrecognized_name` alert. It is also called when the client did not send a name:
+    /// ```
+    /// # fn main() -> std::io::Result<()> {
+    /// # use openssl_lite::SslCtx;
+    /// let example = SslCtx::new()?;
+    /// let mut ctx = SslCtx::new()?;
+    /// ctx.set_servername_callback(move |req| match req.servername() {
+    ///     Some("example.com") => req.set_ctx(&example).is_ok(),
+    ///     _ => true,
+    /// });
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[doc(alias = "SSL_CTX_set_tlsext_servername_callback")]
+    pub fn set_servername_callback<F>(&mut self, callback: F)
+    where F: Fn(&mut SniRequest) -> bool + Send + Sync + 'static {
+        unsafe {
+            ex_data::ctx_set(self.0, ServernameCallback::new(callback));
+            sys::SSL_CTX_set_tlsext_servername_callback(self.0, Some(servername_trampoline));
+        }
+    }
+
+    /// Sets the callback receiving TLS secrets in the NSS key log format, one line per call
+    ///
+    /// Anyone who gets these lines can decrypt the traffic, use this only for debugging.
+    /// A panic in the callback is resumed by the [`Ssl`](crate::Ssl) method that performed the handshake
+    #[doc(alias = "SSL_CTX_set_keylog_callback")]
+    pub fn set_keylog_callback<F>(&mut self, callback: F)
+    where F: Fn(&str) + Send + Sync + 'static {
+        unsafe {
+            ex_data::ctx_set(self.0, KeylogCallback(Box::new(callback)));
+            sys::SSL_CTX_set_keylog_callback(self.0, Some(keylog_trampoline));
+        }
+    }
+
+    /// Sets the callback notified about handshake progress and alerts
+    ///
+    /// Example: print alerts sent and received
+    /// ```
+    /// # fn main() -> std::io::Result<()> {
+    /// # use openssl_lite::SslCtx;
+    /// let mut ctx = SslCtx::new()?;
+    /// ctx.set_info_callback(|event| if let Some(alert) = event.alert() {
+    ///     eprintln!("alert: {}, received: {}", alert.description, alert.received);
+    /// });
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[doc(alias = "SSL_CTX_set_info_callback")]
+    pub fn set_info_callback<F>(&mut self, callback: F)
+    where F: Fn(&InfoEvent) + Send + Sync + 'static {
+        unsafe {
+            ex_data::ctx_set(self.0, InfoCallback::new(callback));
+            sys::SSL_CTX_set_info_callback(self.0, Some(info_trampoline));
+        }
+    }
+
+    /// Sets the callback called with every protocol message sent or received, including record headers
+    #[doc(alias = "SSL_CTX_set_msg_callback")]
+    pub fn set_msg_callback<F>(&mut self, callback: F)
+    where F: Fn(&Message) + Send + Sync + 'static {
+        unsafe {
+            ex_data::ctx_set(self.0, MsgCallback::new(callback));
+            sys::SSL_CTX_set_msg_callback(self.0, Some(msg_trampoline));
+        }
+    }
+
+    /// Appends TLS secrets to the file, which can be loaded into Wireshark to decrypt the traffic
+    pub fn set_keylog_file<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+        let mut options = OpenOptions::new();
+        options.append(true).create(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let file = Mutex::new(options.open(path)?);
+
+        self.set_keylog_callback(move |line| {
+            let mut file = file.lock().unwrap_or_else(|e| e.into_inner());
+            // Logging is best effort, a failed write must not break the connection
+            let _ = writeln!(file, "{line}");
+        });
         Ok(())
     }
+
+    /// Calls [`SslCtx::set_keylog_file`] with the file named by the `SSLKEYLOGFILE` environment variable
+    ///
+    /// Returns false without doing anything if the variable is not set
+    pub fn set_keylog_file_from_env(&mut self) -> io::Result<bool> {
+        match std::env::var_os("SSLKEYLOGFILE") {
+            Some(path) if !path.is_empty() => self.set_keylog_file(path).map(|()| true),
+            _ => Ok(false),
+        }
+    }
+}
+
+fn alpn_wire_format(protocols: &[&[u8]]) -> Result<Vec<u8>, ErrorStack> {
+    let mut wire = vec![];
+    for proto in protocols {
+        if proto.is_empty() || proto.len() > 255 {
+            return Err(ErrorStack::custom(format!("invalid ALPN protocol length {}", proto.len())));
+        }
+        wire.push(proto.len() as u8);
+        wire.extend_from_slice(proto);
+    }
+    Ok(wire)
+}
+
+unsafe extern "C" fn ctx_verify_trampoline(preverify_ok: c_int, x509_ctx: *mut sys::X509_STORE_CTX) -> c_int {
+    let store = unsafe { X509StoreContextRef::from_ptr(x509_ctx) };
+    // The verify callback was copied into the SSL object, it belongs to the original context after an SNI switch
+    let ctx = unsafe { original_ctx(store.ssl()) };
+    let ok = match unsafe { ex_data::ctx_get::<VerifyCallback>(ctx) } {
+        Some(callback) => unsafe { callback.call(preverify_ok, store) },
+        None => preverify_ok == 1,
+    };
+    record_verify_result(ok, store)
+}
+
+pub(crate) unsafe extern "C" fn ssl_verify_trampoline(preverify_ok: c_int, x509_ctx: *mut sys::X509_STORE_CTX) -> c_int {
+    let store = unsafe { X509StoreContextRef::from_ptr(x509_ctx) };
+    let ok = match unsafe { ex_data::ssl_get::<VerifyCallback>(store.ssl()) } {
+        Some(callback) => unsafe { callback.call(preverify_ok, store) },
+        None => preverify_ok == 1,
+    };
+    record_verify_result(ok, store)
+}
+
+/// Remembers the certificate that failed verification, to be reported by [`SslError::Verify`](crate::SslError::Verify)
+fn record_verify_result(ok: bool, store: &X509StoreContextRef) -> c_int {
+    if !ok {
+        let error = store.error().unwrap_or(X509VerifyError::ApplicationVerification);
+        let subject = store.current_cert().map(|cert| cert.subject_name().to_string());
+        unsafe { ex_data::ssl_set(store.ssl(), VerifyError::new(error, store.error_depth(), subject)) };
+    }
+    ok as c_int
+}
+
+impl VerifyCallback {
+    pub(crate) fn new<F>(callback: F) -> VerifyCallback
+    where F: Fn(bool, &X509StoreContextRef) -> bool + Send + Sync + 'static {
+        VerifyCallback(Box::new(callback))
+    }
+
+    /// Runs the closure, a panic rejects the certificate
+    unsafe fn call(&self, preverify_ok: c_int, store: &X509StoreContextRef) -> bool {
+        unsafe { catch_callback(store.ssl(), || (self.0)(preverify_ok == 1, store)) }.unwrap_or(false)
+    }
+}
+
+unsafe extern "C" fn alpn_select_trampoline(ssl: *mut sys::SSL, out: *mut *const u8, outlen: *mut u8, inp: *const u8, inlen: u32, _arg: *mut c_void) -> c_int {
+    let ctx = unsafe { sys::SSL_get_SSL_CTX(ssl) };
+    let Some(callback) = (unsafe { ex_data::ctx_get::<AlpnSelectCallback>(ctx) }) else {
+        return sys::SSL_TLSEXT_ERR_NOACK;
+    };
+
+    let mut wire = unsafe { core::slice::from_raw_parts(inp, inlen as usize) };
+    let mut offered = vec![];
+    while let Some((&len, rest)) = wire.split_first() {
+        let Some((proto, rest)) = rest.split_at_checked(len as usize) else { break };
+        offered.push(proto);
+        wire = rest;
+    }
+
+    match unsafe { catch_callback(ssl, || (callback.0)(&offered)) } {
+        // Panicked
+        None => sys::SSL_TLSEXT_ERR_ALERT_FATAL,
+        Some(None) => sys::SSL_TLSEXT_ERR_NOACK,
+        // The client would reject a protocol it did not offer
+        Some(Some(proto)) => match offered.iter().find(|&&p| p == proto) {
+            Some(proto) => {
+                unsafe {
+                    *out = proto.as_ptr();
+                    *outlen = proto.len() as u8;
+                }
+                sys::SSL_TLSEXT_ERR_OK
+            }
+            None => sys::SSL_TLSEXT_ERR_ALERT_FATAL,
+        },
+    }
+}
+
+unsafe extern "C" fn keylog_trampoline(ssl: *const sys::SSL, line: *const c_char) {
+    let ctx = unsafe { sys::SSL_get_SSL_CTX(ssl) };
+    if let Some(callback) = unsafe { ex_data::ctx_get::<KeylogCallback>(ctx) }
+        && let Ok(line) = unsafe { CStr::from_ptr(line) }.to_str() {
+        // Nothing to fail here, the panic is resumed when the handshake call returns
+        unsafe { catch_callback(ssl, || (callback.0)(line)) };
+    }
 }
 
 impl Drop for SslCtx {
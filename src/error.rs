@@ -1,32 +1,79 @@
 use core::fmt;
-use core::ffi::{CStr, c_char};
+use core::ffi::{c_int, c_ulong};
+use std::borrow::Cow;
 use std::io::{self, ErrorKind};
 use std::error::Error;
 
-use crate::sys;
+use crate::{sys, X509VerifyError};
+use crate::info::str_from_ptr;
 
-/// OpenSSL error stack
-#[derive(Debug)]
-pub struct ErrorStack(pub Vec<String>);
+/// OpenSSL error stack, oldest error first
+#[derive(Debug, Clone)]
+pub struct ErrorStack(Vec<ErrorEntry>);
 
 impl ErrorStack {
     /// Retrieves the error stack. Usually, you don't need this
+    #[doc(alias = "ERR_get_error_all")]
     pub fn get() -> ErrorStack {
         let mut errors = vec![];
-        while let Some(err) = get_error() {
+        while let Some(err) = ErrorEntry::get() {
             errors.push(err);
         }
         ErrorStack(errors)
     }
+
+    /// Discards errors left in the thread-local queue by earlier calls
+    ///
+    /// Called before every operation, so that its result is not mixed up with unrelated errors
+    #[doc(alias = "ERR_clear_error")]
+    pub(crate) fn clear() {
+        unsafe { sys::ERR_clear_error() };
+    }
+
+    /// Error raised by this crate rather than OpenSSL
+    pub(crate) fn custom(reason: impl Into<String>) -> ErrorStack {
+        ErrorStack(vec![ErrorEntry {
+            code: 0,
+            reason: Some(Cow::Owned(reason.into())),
+            file: None,
+            line: 0,
+            function: None,
+            data: None,
+        }])
+    }
+
+    /// Returns all errors of the stack
+    pub fn errors(&self) -> &[ErrorEntry] {
+        &self.0
+    }
+
+    /// Returns the fatal alert received from the peer, if it caused the error
+    pub(crate) fn peer_alert(&self) -> Option<u8> {
+        self.0.iter().find_map(|e| {
+            let alert = e.reason_code() - sys::SSL_AD_REASON_OFFSET;
+            let is_alert = e.code != 0 && e.library_code() == sys::ERR_LIB_SSL && (0..=255).contains(&alert);
+            is_alert.then_some(alert as u8)
+        })
+    }
+
+    /// Returns true if any error matches the library and reason, from [`crate::reason`]
+    ///
+    /// Example: `err.contains(reason::ERR_LIB_SSL, reason::SSL_R_CERTIFICATE_VERIFY_FAILED)`
+    pub fn contains(&self, library: i32, reason: i32) -> bool {
+        self.0.iter().any(|e| e.code != 0 && e.library_code() == library && e.reason_code() == reason)
+    }
 }
 
 impl fmt::Display for ErrorStack {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         if self.0.is_empty() {
-            f.write_str("Empty SSL error stack")
-        } else {
-            f.write_str(&self.0[0])
+            return f.write_str("Empty SSL error stack");
+        }
+        for (i, err) in self.0.iter().enumerate() {
+            if i > 0 { f.write_str(", ")?; }
+            write!(f, "{err}")?;
         }
+        Ok(())
     }
 }
 
@@ -38,32 +85,143 @@ impl From<ErrorStack> for io::Error {
     }
 }
 
-fn get_error() -> Option<String> {
-    let code = unsafe { sys::ERR_get_error() };
-    if code == 0 { return None; }
+/// Single entry of the [`ErrorStack`]
+#[derive(Debug, Clone)]
+pub struct ErrorEntry {
+    code: c_ulong,
+    reason: Option<Cow<'static, str>>,
+    file: Option<String>,
+    line: u32,
+    function: Option<String>,
+    data: Option<String>,
+}
+
+impl ErrorEntry {
+    fn get() -> Option<ErrorEntry> {
+        let mut file = core::ptr::null();
+        let mut line = 0;
+        let mut function = core::ptr::null();
+        let mut data = core::ptr::null();
+        let mut flags = 0;
+        let code = unsafe { sys::ERR_get_error_all(&mut file, &mut line, &mut function, &mut data, &mut flags) };
+        if code == 0 { return None; }
+
+        let reason = match unsafe { str_from_ptr(sys::ERR_reason_error_string(code)) } {
+            Some(reason) => Some(Cow::Borrowed(reason)),
+            // System errors store errno in place of the reason
+            None if sys::ERR_GET_LIB(code) == sys::ERR_LIB_SYS => {
+                Some(Cow::Owned(io::Error::from_raw_os_error(sys::ERR_GET_REASON(code)).to_string()))
+            }
+            None => None,
+        };
+        let data = if flags & sys::ERR_TXT_STRING != 0 { unsafe { str_from_ptr(data) } } else { None };
+        Some(ErrorEntry {
+            code,
+            reason,
+            file: unsafe { str_from_ptr(file) }.map(str::to_string),
+            line: line.max(0) as u32,
+            function: unsafe { str_from_ptr(function) }.filter(|f| !f.is_empty()).map(str::to_string),
+            data: data.filter(|d| !d.is_empty()).map(str::to_string),
+        })
+    }
 
-    let mut buf = [0u8; 256];
-    unsafe { sys::ERR_error_string_n(code, buf.as_mut_ptr() as *mut c_char, buf.len()); }
+    /// Packed error code, as returned by `ERR_get_error`. Zero for errors raised by this crate
+    pub fn code(&self) -> c_ulong {
+        self.code
+    }
 
-    let s = match CStr::from_bytes_until_nul(&buf) {
-        Ok(s) => s.to_string_lossy().into_owned(),
-        Err(_) => "Error too long".to_string(), // Never happens
-    };
+    /// Library that raised the error, compare with `ERR_LIB_*` from [`crate::reason`]
+    #[doc(alias = "ERR_GET_LIB")]
+    pub fn library_code(&self) -> i32 {
+        sys::ERR_GET_LIB(self.code)
+    }
 
-    Some(s)
+    /// Library name, e.g. `SSL routines`
+    #[doc(alias = "ERR_lib_error_string")]
+    pub fn library(&self) -> Option<&'static str> {
+        if self.code == 0 { return None; }
+        unsafe { str_from_ptr(sys::ERR_lib_error_string(self.code)) }
+    }
+
+    /// Reason of the error, compare with the constants from [`crate::reason`]. For system errors, it is `errno`
+    #[doc(alias = "ERR_GET_REASON")]
+    pub fn reason_code(&self) -> i32 {
+        sys::ERR_GET_REASON(self.code)
+    }
+
+    /// Reason description, e.g. `certificate verify failed`
+    #[doc(alias = "ERR_reason_error_string")]
+    pub fn reason(&self) -> Option<&str> {
+        self.reason.as_deref()
+    }
+
+    /// OpenSSL source file that raised the error
+    pub fn file(&self) -> Option<&str> {
+        self.file.as_deref()
+    }
+
+    /// Line in [`ErrorEntry::file`]
+    pub fn line(&self) -> Option<u32> {
+        self.file.as_ref().map(|_| self.line)
+    }
+
+    /// OpenSSL function that raised the error
+    pub fn function(&self) -> Option<&str> {
+        self.function.as_deref()
+    }
+
+    /// Additional details, e.g. the name of a file that failed to load
+    pub fn data(&self) -> Option<&str> {
+        self.data.as_deref()
+    }
+}
+
+// Formatted like `ERR_error_string_n`, with the function and data filled in
+impl fmt::Display for ErrorEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.code == 0 {
+            return f.write_str(self.reason().unwrap_or("unknown error"));
+        }
+
+        write!(f, "error:{:08X}:", self.code)?;
+        match self.library() {
+            Some(lib) => f.write_str(lib)?,
+            None => write!(f, "lib({})", self.library_code())?,
+        }
+        write!(f, ":{}:", self.function().unwrap_or(""))?;
+        match self.reason() {
+            Some(reason) => f.write_str(reason)?,
+            None => write!(f, "reason({})", self.reason_code())?,
+        }
+        if let Some(data) = self.data() {
+            write!(f, ":{data}")?;
+        }
+        Ok(())
+    }
 }
 
 /// Error returned by the SSL object methods
 ///
-/// Can be automatically converted to [`std::io::Error`]
+/// Can be automatically converted to [`std::io::Error`]. Except for `Syscall`, which is already an I/O error,
+/// the `SslError` is kept inside and can be recovered with [`io::Error::downcast`]
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum SslError {
-    /// `SSL_ERROR_ZERO_RETURN`: The socket does not have any more data because it has been closed
+    /// `SSL_ERROR_ZERO_RETURN`: The peer has closed the TLS session with `close_notify`
+    ///
+    /// Reads return `Ok(0)` instead, as this is a clean end of stream. Other operations convert it to
+    /// [`ErrorKind::BrokenPipe`], like a socket the peer has shut down
     ZeroReturn,
     /// `SSL_ERROR_SYSCALL`: A fatal I/O error occured, and no more operations should be performed on this object
     Syscall(io::Error),
     /// `SSL_ERROR_SSL`: Non-recoverable protocol error
     Ssl(ErrorStack),
+    /// `SSL_ERROR_SSL` caused by a peer certificate that failed verification
+    Verify(VerifyError),
+    /// `SSL_ERROR_SSL` caused by a fatal alert from the peer, e.g. when it rejected our certificate
+    PeerAlert(PeerAlert),
+    /// The connection was closed without `close_notify`, so the received data may be truncated
+    UnexpectedEof,
     /// `SSL_ERROR_WANT_READ`: The operation was not completed and can be retried after socket becomes readable
     WantRead,
     /// `SSL_ERROR_WANT_WRITE`: Same as `WantRead`, but socket has to become writable
@@ -75,13 +233,21 @@ pub enum SslError {
 impl From<SslError> for io::Error {
     fn from(err: SslError) -> io::Error {
         use SslError::*;
-        match err {
-            ZeroReturn => ErrorKind::UnexpectedEof.into(),
-            Syscall(err) => err,
-            Ssl(es) => es.into(),
-            WantRead | WantWrite => ErrorKind::WouldBlock.into(),
-            Other => ErrorKind::Other.into(),
-        }
+        let kind = match err {
+            Syscall(err) => return err,
+            ZeroReturn => ErrorKind::BrokenPipe,
+            Ssl(_) | Verify(_) | PeerAlert(_) => ErrorKind::InvalidData,
+            UnexpectedEof => ErrorKind::UnexpectedEof,
+            WantRead | WantWrite => ErrorKind::WouldBlock,
+            Other => ErrorKind::Other,
+        };
+        io::Error::new(kind, err)
+    }
+}
+
+impl From<ErrorStack> for SslError {
+    fn from(es: ErrorStack) -> SslError {
+        SslError::Ssl(es)
     }
 }
 
@@ -98,6 +264,9 @@ impl fmt::Display for SslError {
             ZeroReturn => f.write_str("zero return"),
             Syscall(err) => write!(f, "syscall: {err}"),
             Ssl(es) => write!(f, "ssl error: {es}"),
+            Verify(err) => write!(f, "ssl error: {err}"),
+            PeerAlert(alert) => write!(f, "ssl error: {alert}"),
+            UnexpectedEof => f.write_str("unexpected eof: connection closed without close_notify"),
             WantRead => f.write_str("want read"),
             WantWrite => f.write_str("want write"),
             Other => f.write_str("other ssl error"),
@@ -105,4 +274,112 @@ impl fmt::Display for SslError {
     }
 }
 
-impl Error for SslError {}
+impl Error for SslError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        use SslError::*;
+        match self {
+            Syscall(err) => Some(err),
+            Ssl(es) => Some(es),
+            Verify(err) => Some(err),
+            PeerAlert(alert) => Some(alert),
+            _ => None,
+        }
+    }
+}
+
+/// Details of a failed certificate verification, see [`SslError::Verify`]
+#[derive(Debug, Clone)]
+pub struct VerifyError {
+    error: X509VerifyError,
+    depth: u32,
+    subject: Option<String>,
+    stack: ErrorStack,
+}
+
+impl VerifyError {
+    pub(crate) fn new(error: X509VerifyError, depth: u32, subject: Option<String>) -> VerifyError {
+        VerifyError { error, depth, subject, stack: ErrorStack(vec![]) }
+    }
+
+    pub(crate) fn with_stack(&self, stack: ErrorStack) -> VerifyError {
+        VerifyError { stack, ..self.clone() }
+    }
+
+    /// Reason of the failure
+    pub fn error(&self) -> X509VerifyError {
+        self.error
+    }
+
+    /// Human-readable reason, e.g. `certificate has expired`
+    pub fn description(&self) -> &'static str {
+        self.error.description()
+    }
+
+    /// Depth of the offending certificate in the chain, 0 is the peer certificate
+    pub fn depth(&self) -> u32 {
+        self.depth
+    }
+
+    /// Subject of the offending certificate, e.g. `CN=example.com`
+    pub fn subject(&self) -> Option<&str> {
+        self.subject.as_deref()
+    }
+
+    /// OpenSSL errors of the failed handshake
+    pub fn error_stack(&self) -> &ErrorStack {
+        &self.stack
+    }
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "certificate verify failed: {} (depth {}", self.description(), self.depth)?;
+        if let Some(subject) = &self.subject {
+            write!(f, ", subject {subject}")?;
+        }
+        f.write_str(")")
+    }
+}
+
+impl Error for VerifyError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// Fatal alert sent by the peer, see [`SslError::PeerAlert`]
+#[derive(Debug, Clone)]
+pub struct PeerAlert {
+    code: u8,
+    stack: ErrorStack,
+}
+
+impl PeerAlert {
+    pub(crate) fn new(code: u8, stack: ErrorStack) -> PeerAlert {
+        PeerAlert { code, stack }
+    }
+
+    /// Alert number, e.g. 48 for `unknown_ca`
+    pub fn code(&self) -> u8 {
+        self.code
+    }
+
+    /// Description, e.g. `unknown CA`
+    #[doc(alias = "SSL_alert_desc_string_long")]
+    pub fn description(&self) -> &'static str {
+        unsafe { str_from_ptr(sys::SSL_alert_desc_string_long(self.code as c_int)) }.unwrap_or("unknown")
+    }
+
+    /// OpenSSL errors of the failed operation
+    pub fn error_stack(&self) -> &ErrorStack {
+        &self.stack
+    }
+}
+
+impl fmt::Display for PeerAlert {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "peer sent alert: {} ({})", self.description(), self.code)
+    }
+}
+
+impl Error for PeerAlert {}
@@ -0,0 +1,93 @@
+//! Storage of Rust values (mostly callbacks) inside OpenSSL objects
+//!
+//! Values are boxed and put into ex_data slots. Every slot has a free callback,
+//! so the value lives exactly as long as the OpenSSL object itself,
+//! even if it outlives the Rust wrapper (e.g. `SSL` keeps its `SSL_CTX` alive)
+
+use core::any::TypeId;
+use core::ffi::{c_int, c_long, c_void};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use crate::sys;
+
+/// Returns the ex_data index reserved for values of type `T` in the given class
+fn index<T: 'static>(class: c_int) -> c_int {
+    static INDEXES: Mutex<BTreeMap<(c_int, TypeId), c_int>> = Mutex::new(BTreeMap::new());
+
+    let mut indexes = INDEXES.lock().unwrap_or_else(|e| e.into_inner());
+    *indexes.entry((class, TypeId::of::<T>())).or_insert_with(|| {
+        let idx = unsafe { sys::CRYPTO_get_ex_new_index(class, 0, core::ptr::null_mut(), None, None, Some(free_box::<T>)) };
+        assert!(idx >= 0, "CRYPTO_get_ex_new_index failed");
+        idx
+    })
+}
+
+unsafe extern "C" fn free_box<T>(_parent: *mut c_void, ptr: *mut c_void, _ad: *mut sys::CRYPTO_EX_DATA, _idx: c_int, _argl: c_long, _argp: *mut c_void) {
+    if !ptr.is_null() {
+        drop(unsafe { Box::from_raw(ptr as *mut T) });
+    }
+}
+
+/// Value stored in a context, followed by the values it replaced
+///
+/// Connections on other threads may still be running a replaced callback,
+/// so replaced values are only dropped together with the context
+struct CtxSlot<T> {
+    value: T,
+    _replaced: Option<Box<CtxSlot<T>>>,
+}
+
+/// Creates the slot for values of type `T` while the context is not shared yet
+///
+/// Storing into a slot for the first time reallocates the slot array of the context,
+/// which callbacks on other threads may be reading at the same time
+pub(crate) unsafe fn ctx_reserve<T: 'static>(ctx: *mut sys::SSL_CTX) {
+    let idx = index::<CtxSlot<T>>(sys::CRYPTO_EX_INDEX_SSL_CTX);
+    let ret = unsafe { sys::SSL_CTX_set_ex_data(ctx, idx, core::ptr::null_mut()) };
+    assert!(ret == 1, "SSL_CTX_set_ex_data failed");
+}
+
+/// Stores a value in the context, keeping the previous one alive until the context is freed
+pub(crate) unsafe fn ctx_set<T: 'static>(ctx: *mut sys::SSL_CTX, value: T) {
+    let idx = index::<CtxSlot<T>>(sys::CRYPTO_EX_INDEX_SSL_CTX);
+    let old = unsafe { sys::SSL_CTX_get_ex_data(ctx, idx) } as *mut CtxSlot<T>;
+    let replaced = (!old.is_null()).then(|| unsafe { Box::from_raw(old) });
+    let slot = Box::new(CtxSlot { value, _replaced: replaced });
+    let ret = unsafe { sys::SSL_CTX_set_ex_data(ctx, idx, Box::into_raw(slot) as *mut c_void) };
+    assert!(ret == 1, "SSL_CTX_set_ex_data failed");
+}
+
+/// Borrows the value stored in the context
+pub(crate) unsafe fn ctx_get<'a, T: 'static>(ctx: *const sys::SSL_CTX) -> Option<&'a T> {
+    let idx = index::<CtxSlot<T>>(sys::CRYPTO_EX_INDEX_SSL_CTX);
+    let ptr = unsafe { sys::SSL_CTX_get_ex_data(ctx, idx) };
+    unsafe { (ptr as *const CtxSlot<T>).as_ref() }.map(|slot| &slot.value)
+}
+
+/// Stores a value in the SSL object, dropping the previous one
+pub(crate) unsafe fn ssl_set<T: 'static>(ssl: *mut sys::SSL, value: T) {
+    let idx = index::<T>(sys::CRYPTO_EX_INDEX_SSL);
+    let old = unsafe { sys::SSL_get_ex_data(ssl, idx) };
+    let ret = unsafe { sys::SSL_set_ex_data(ssl, idx, Box::into_raw(Box::new(value)) as *mut c_void) };
+    assert!(ret == 1, "SSL_set_ex_data failed");
+    if !old.is_null() {
+        drop(unsafe { Box::from_raw(old as *mut T) });
+    }
+}
+
+/// Borrows the value stored in the SSL object
+pub(crate) unsafe fn ssl_get<'a, T: 'static>(ssl: *const sys::SSL) -> Option<&'a T> {
+    let idx = index::<T>(sys::CRYPTO_EX_INDEX_SSL);
+    let ptr = unsafe { sys::SSL_get_ex_data(ssl, idx) };
+    unsafe { (ptr as *const T).as_ref() }
+}
+
+/// Removes the value from the SSL object
+pub(crate) unsafe fn ssl_take<T: 'static>(ssl: *mut sys::SSL) -> Option<T> {
+    let idx = index::<T>(sys::CRYPTO_EX_INDEX_SSL);
+    let ptr = unsafe { sys::SSL_get_ex_data(ssl, idx) };
+    if ptr.is_null() { return None; }
+    unsafe { sys::SSL_set_ex_data(ssl, idx, core::ptr::null_mut()) };
+    Some(*unsafe { Box::from_raw(ptr as *mut T) })
+}
@@ -0,0 +1,99 @@
+use core::fmt;
+use core::ffi::{CStr, c_char, c_int};
+
+use crate::sys;
+use crate::x509::nid2sn;
+
+/// Cipher suite negotiated for the connection
+#[repr(transparent)]
+pub struct SslCipherRef(sys::SSL_CIPHER);
+
+impl SslCipherRef {
+    pub(crate) unsafe fn from_ptr<'a>(ptr: *const sys::SSL_CIPHER) -> &'a SslCipherRef {
+        unsafe { &*(ptr as *const SslCipherRef) }
+    }
+
+    fn as_ptr(&self) -> *const sys::SSL_CIPHER {
+        self as *const SslCipherRef as *const sys::SSL_CIPHER
+    }
+
+    /// OpenSSL name, e.g. `ECDHE-RSA-AES128-GCM-SHA256`
+    #[doc(alias = "SSL_CIPHER_get_name")]
+    pub fn name(&self) -> &str {
+        unsafe { str_from_ptr(sys::SSL_CIPHER_get_name(self.as_ptr())) }.unwrap_or("(NONE)")
+    }
+
+    /// IANA (RFC) name, e.g. `TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256`
+    #[doc(alias = "SSL_CIPHER_standard_name")]
+    pub fn standard_name(&self) -> Option<&str> {
+        unsafe { str_from_ptr(sys::SSL_CIPHER_standard_name(self.as_ptr())) }
+    }
+
+    /// Two-byte IANA identifier, e.g. `0x1301` for `TLS_AES_128_GCM_SHA256`
+    #[doc(alias = "SSL_CIPHER_get_protocol_id")]
+    pub fn protocol_id(&self) -> u16 {
+        unsafe { sys::SSL_CIPHER_get_protocol_id(self.as_ptr()) }
+    }
+
+    /// Secret bits of the symmetric cipher
+    #[doc(alias = "SSL_CIPHER_get_bits")]
+    pub fn bits(&self) -> u32 {
+        unsafe { sys::SSL_CIPHER_get_bits(self.as_ptr(), core::ptr::null_mut()) as u32 }
+    }
+
+    /// Minimal protocol version of the cipher, e.g. `TLSv1.2`
+    #[doc(alias = "SSL_CIPHER_get_version")]
+    pub fn version(&self) -> &str {
+        unsafe { str_from_ptr(sys::SSL_CIPHER_get_version(self.as_ptr())) }.unwrap_or("(NONE)")
+    }
+}
+
+impl fmt::Debug for SslCipherRef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SslCipherRef")
+            .field("name", &self.name())
+            .field("protocol_id", &format_args!("{:#06x}", self.protocol_id()))
+            .field("bits", &self.bits())
+            .finish()
+    }
+}
+
+/// Signature algorithm used by the peer in the handshake
+///
+/// Displayed in the OpenSSL signature algorithms list format, e.g. `ECDSA+SHA256`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SignatureAlgorithm {
+    /// Signature scheme, e.g. `RSA-PSS`, `ECDSA` or `Ed25519`
+    pub signature: &'static str,
+    /// Digest, if it is separate from the scheme (unlike Ed25519)
+    pub digest: Option<&'static str>,
+}
+
+impl SignatureAlgorithm {
+    pub(crate) fn from_nids(sig: c_int, digest: c_int) -> Option<SignatureAlgorithm> {
+        let signature = match sig {
+            sys::NID_rsaEncryption => "RSA",
+            sys::NID_rsassaPss => "RSA-PSS",
+            sys::NID_X9_62_id_ecPublicKey => "ECDSA",
+            sys::NID_ED25519 => "Ed25519",
+            sys::NID_ED448 => "Ed448",
+            sys::NID_dsa => "DSA",
+            nid => nid2sn(nid)?,
+        };
+        Some(SignatureAlgorithm { signature, digest: nid2sn(digest) })
+    }
+}
+
+impl fmt::Display for SignatureAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.digest {
+            Some(digest) => write!(f, "{}+{}", self.signature, digest),
+            None => f.write_str(self.signature),
+        }
+    }
+}
+
+pub(crate) unsafe fn str_from_ptr<'a>(ptr: *const c_char) -> Option<&'a str> {
+    if ptr.is_null() { return None; }
+    unsafe { CStr::from_ptr(ptr) }.to_str().ok()
+}
@@ -8,27 +8,129 @@
 //! `cargo build --release --features=cmd`
 
 pub(crate) mod sys;
+mod ex_data;
+mod bio;
 
 mod error;
-pub use error::{ErrorStack, SslError};
+pub use error::{ErrorStack, ErrorEntry, SslError, VerifyError, PeerAlert};
+mod x509;
+pub use x509::{X509, X509Ref, X509NameRef, X509StoreContextRef, X509VerifyError, AltName};
+mod pkey;
+pub use pkey::PKey;
+mod session;
+pub use session::{SslSession, SessionCacheMode, TicketKey, TicketKeys};
 mod ctx;
-pub use ctx::SslCtx;
+pub use ctx::{SslCtx, FileType, VerifyMode};
+mod sni;
+pub use sni::SniRequest;
+mod info;
+pub use info::{SslCipherRef, SignatureAlgorithm};
+mod trace;
+pub use trace::{InfoEvent, Alert, Message, ContentType};
 mod ssl;
-pub use ssl::Ssl;
+pub use ssl::{Ssl, ShutdownState};
+mod stream;
+pub use stream::{SslStream, HandshakeResult, MidHandshake};
+mod connector;
+pub use connector::{TlsConnector, TlsAcceptor};
 
 #[cfg(feature = "tokio")]
 mod async_ssl;
 #[cfg(feature = "tokio")]
 pub use async_ssl::AsyncSsl;
 
-/// TLS versions for [`SslCtx::set_min_version`]
+/// Protocol versions for [`SslCtx::set_min_version`] and [`SslCtx::set_max_version`]
 pub mod version {
+    use core::fmt;
     use core::ffi::c_long;
 
+    #[deprecated(note = "use `TlsVersion`")]
+    pub const SSL3_VERSION: c_long = 0x0300;
+    #[deprecated(note = "use `TlsVersion`")]
     pub const TLS1_VERSION: c_long = 0x0301;
+    #[deprecated(note = "use `TlsVersion`")]
     pub const TLS1_1_VERSION: c_long = 0x0302;
+    #[deprecated(note = "use `TlsVersion`")]
     pub const TLS1_2_VERSION: c_long = 0x0303;
+    #[deprecated(note = "use `TlsVersion`")]
     pub const TLS1_3_VERSION: c_long = 0x0304;
+    #[deprecated(note = "use `TlsVersion`")]
+    pub const DTLS1_VERSION: c_long = 0xFEFF;
+    #[deprecated(note = "use `TlsVersion`")]
+    pub const DTLS1_2_VERSION: c_long = 0xFEFD;
+
+    /// Protocol version, as returned by [`crate::Ssl::version`]
+    ///
+    /// Ordering is only meaningful between versions of the same protocol (TLS or DTLS)
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    pub enum TlsVersion {
+        Ssl3,
+        Tls1,
+        Tls1_1,
+        Tls1_2,
+        Tls1_3,
+        /// DTLS versions are only reported by sessions, [`crate::SslCtx`] speaks TLS only
+        Dtls1,
+        Dtls1_2,
+    }
+
+    #[allow(deprecated)]
+    impl TlsVersion {
+        /// Converts a protocol version number used by OpenSSL
+        pub fn from_raw(ver: c_long) -> Option<TlsVersion> {
+            match ver {
+                SSL3_VERSION => Some(TlsVersion::Ssl3),
+                TLS1_VERSION => Some(TlsVersion::Tls1),
+                TLS1_1_VERSION => Some(TlsVersion::Tls1_1),
+                TLS1_2_VERSION => Some(TlsVersion::Tls1_2),
+                TLS1_3_VERSION => Some(TlsVersion::Tls1_3),
+                DTLS1_VERSION => Some(TlsVersion::Dtls1),
+                DTLS1_2_VERSION => Some(TlsVersion::Dtls1_2),
+                _ => None,
+            }
+        }
+
+        /// Returns the protocol version number used by OpenSSL
+        pub fn as_raw(self) -> c_long {
+            match self {
+                TlsVersion::Ssl3 => SSL3_VERSION,
+                TlsVersion::Tls1 => TLS1_VERSION,
+                TlsVersion::Tls1_1 => TLS1_1_VERSION,
+                TlsVersion::Tls1_2 => TLS1_2_VERSION,
+                TlsVersion::Tls1_3 => TLS1_3_VERSION,
+                TlsVersion::Dtls1 => DTLS1_VERSION,
+                TlsVersion::Dtls1_2 => DTLS1_2_VERSION,
+            }
+        }
+    }
+
+    impl fmt::Display for TlsVersion {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str(match self {
+                TlsVersion::Ssl3 => "SSLv3",
+                TlsVersion::Tls1 => "TLSv1",
+                TlsVersion::Tls1_1 => "TLSv1.1",
+                TlsVersion::Tls1_2 => "TLSv1.2",
+                TlsVersion::Tls1_3 => "TLSv1.3",
+                TlsVersion::Dtls1 => "DTLSv1",
+                TlsVersion::Dtls1_2 => "DTLSv1.2",
+            })
+        }
+    }
+}
+pub use version::TlsVersion;
+
+/// Common library and reason codes for [`ErrorStack::contains`] and [`ErrorEntry`]
+pub mod reason {
+    pub const ERR_LIB_SYS: i32 = 2;
+    pub const ERR_LIB_X509: i32 = 11;
+    pub const ERR_LIB_SSL: i32 = 20;
+
+    pub const SSL_R_CERTIFICATE_VERIFY_FAILED: i32 = 134;
+    pub const SSL_R_NO_PROTOCOLS_AVAILABLE: i32 = 191;
+    pub const SSL_R_NO_SHARED_CIPHER: i32 = 193;
+    pub const SSL_R_WRONG_VERSION_NUMBER: i32 = 267;
+    pub const SSL_R_UNEXPECTED_EOF_WHILE_READING: i32 = 294;
 }
 
 /// Available options for [`SslCtx::set_options`]. Only for legacy compatibility
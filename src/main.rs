@@ -1,6 +1,6 @@
 use std::io;
 
-use openssl_lite::{SslCtx, Ssl, AsyncSsl};
+use openssl_lite::{SslCtx, SslError, TlsConnector, TlsAcceptor};
 use openssl_lite::op::*;
 
 fn help() -> ! {
@@ -10,6 +10,7 @@ fn help() -> ! {
     eprintln!("    s_server <addr>");
     eprintln!("Add flag `-tokio` to use async version");
     eprintln!("Add flag `-insecure` to disable peer verification");
+    eprintln!("Add flag `-keylogfile <file>` to append TLS secrets to the file");
     std::process::exit(1)
 }
 
@@ -21,25 +22,27 @@ fn main() -> io::Result<()> {
     let mut tokio = false;
     let mut insecure = false;
     let mut ign_eof = false;
+    let mut keylog = None;
     let mut addr = String::new();
-    for a in args {
+    while let Some(a) = args.next() {
         match a.as_str() {
             "-tokio" => tokio = true,
             "-insecure" => insecure = true,
             "-ign_eof" => ign_eof = true,
+            "-keylogfile" => keylog = Some(args.next().unwrap_or_else(|| help())),
             a if addr.is_empty() => addr = a.to_string(),
             _ => help(),
         }
     }
 
     if cmd == "s_client" && !tokio {
-        s_client(&addr, insecure, ign_eof)?;
+        s_client(&addr, insecure, ign_eof, keylog.as_deref())?;
     } else if cmd == "s_client" && tokio {
-        s_client_tokio(&addr, insecure, ign_eof)?;
+        s_client_tokio(&addr, insecure, ign_eof, keylog.as_deref())?;
     } else if cmd == "s_server" && !tokio {
-        s_server(&addr)?;
+        s_server(&addr, keylog.as_deref())?;
     } else if cmd == "s_server" && tokio {
-        s_server_tokio(&addr)?;
+        s_server_tokio(&addr, keylog.as_deref())?;
     } else {
         eprintln!("Unknown command!");
         help();
@@ -48,24 +51,38 @@ fn main() -> io::Result<()> {
     Ok(())
 }
 
-fn s_client(addr: &str, insecure: bool, ign_eof: bool) -> io::Result<()> {
+/// Extracts the host from `host:port` or `[ipv6]:port`
+fn host_of(addr: &str) -> &str {
+    if let Some((host, _)) = addr.strip_prefix('[').and_then(|a| a.split_once(']')) {
+        return host;
+    }
+    addr.rsplit_once(':').map_or(addr, |(host, _)| host)
+}
+
+/// Prints the certificate that failed verification, like `openssl s_client`
+fn print_verify_error(err: &SslError) {
+    if let SslError::Verify(err) = err {
+        eprintln!("depth={} {}", err.depth(), err.subject().unwrap_or(""));
+        eprintln!("verify error:num={}:{}", err.error().as_raw(), err.description());
+        eprintln!("Verify return code: {} ({})", err.error().as_raw(), err.description());
+    }
+}
+
+fn s_client(addr: &str, insecure: bool, ign_eof: bool, keylog: Option<&str>) -> io::Result<()> {
     use std::io::{Read, Write};
     use std::net::TcpStream;
-    use std::ffi::CString;
 
     let mut ctx = SslCtx::new()?;
     ctx.set_default_verify_paths()?;
     ctx.set_verify(!insecure);
     if ign_eof { ctx.set_options(SSL_OP_IGNORE_UNEXPECTED_EOF); }
+    if let Some(file) = keylog { ctx.set_keylog_file(file)?; }
 
     let sock = TcpStream::connect(addr)?;
     sock.set_nodelay(true)?;
 
-    let domain = addr.split(':').next().unwrap();
-    let mut ssl = Ssl::new(&ctx)?;
-    ssl.set_hostname(&CString::new(domain).unwrap())?;
-    ssl.set_fd(&sock)?; // This calls AsRawFd
-    ssl.connect()?;
+    let domain = host_of(addr);
+    let mut ssl = TlsConnector::from_ctx(ctx).connect(domain, sock).inspect_err(print_verify_error)?;
 
     // Read stdin
     let mut buf = vec![];
@@ -81,15 +98,14 @@ fn s_client(addr: &str, insecure: bool, ign_eof: bool) -> io::Result<()> {
     Ok(())
 }
 
-fn s_client_tokio(addr: &str, insecure: bool, ign_eof: bool) -> io::Result<()> {
+fn s_client_tokio(addr: &str, insecure: bool, ign_eof: bool, keylog: Option<&str>) -> io::Result<()> {
     tokio::runtime::Builder::new_multi_thread()
         .enable_all()
         .build()?
-        .block_on(s_client_tokio_async(addr, insecure, ign_eof))
+        .block_on(s_client_tokio_async(addr, insecure, ign_eof, keylog))
 }
 
-async fn s_client_tokio_async(addr: &str, insecure: bool, ign_eof: bool) -> io::Result<()> {
-    use std::ffi::CString;
+async fn s_client_tokio_async(addr: &str, insecure: bool, ign_eof: bool, keylog: Option<&str>) -> io::Result<()> {
     use tokio::net::TcpStream;
     use tokio::io::{AsyncReadExt, AsyncWriteExt};
 
@@ -97,14 +113,13 @@ async fn s_client_tokio_async(addr: &str, insecure: bool, ign_eof: bool) -> io::
     ctx.set_default_verify_paths()?;
     ctx.set_verify(!insecure);
     if ign_eof { ctx.set_options(SSL_OP_IGNORE_UNEXPECTED_EOF); }
+    if let Some(file) = keylog { ctx.set_keylog_file(file)?; }
 
     let sock = TcpStream::connect(addr).await?;
     sock.set_nodelay(true)?;
 
-    let domain = addr.split(':').next().unwrap();
-    let mut ssl = AsyncSsl::new(&ctx, sock)?;
-    ssl.set_hostname(&CString::new(domain).unwrap())?;
-    ssl.connect().await?;
+    let domain = host_of(addr);
+    let mut ssl = TlsConnector::from_ctx(ctx).connect_async(domain, sock).await.inspect_err(print_verify_error)?;
 
     // Read stdin
     let mut buf = vec![];
@@ -120,21 +135,20 @@ async fn s_client_tokio_async(addr: &str, insecure: bool, ign_eof: bool) -> io::
     Ok(())
 }
 
-fn s_server(addr: &str) -> io::Result<()> {
+fn s_server(addr: &str, keylog: Option<&str>) -> io::Result<()> {
     use std::io::{Read, Write};
     use std::net::TcpListener;
 
     let mut ctx = SslCtx::new()?;
     ctx.load_certificate_chain(c"cert.pem", c"key.pem")?;
+    if let Some(file) = keylog { ctx.set_keylog_file(file)?; }
 
     let sock = TcpListener::bind(addr)?;
 
     let (conn, addr) = sock.accept()?;
     eprintln!("[*] Connection from {addr}");
     conn.set_nodelay(true)?;
-    let mut ssl = Ssl::new(&ctx)?;
-    ssl.set_fd(&conn)?;
-    ssl.accept()?;
+    let mut ssl = TlsAcceptor::from_ctx(ctx).accept(conn)?;
 
     // Read stdin
     let mut buf = vec![];
@@ -150,27 +164,27 @@ fn s_server(addr: &str) -> io::Result<()> {
     Ok(())
 }
 
-fn s_server_tokio(addr: &str) -> io::Result<()> {
+fn s_server_tokio(addr: &str, keylog: Option<&str>) -> io::Result<()> {
     tokio::runtime::Builder::new_multi_thread()
         .enable_all()
         .build()?
-        .block_on(s_server_tokio_async(addr))
+        .block_on(s_server_tokio_async(addr, keylog))
 }
 
-async fn s_server_tokio_async(addr: &str) -> io::Result<()> {
+async fn s_server_tokio_async(addr: &str, keylog: Option<&str>) -> io::Result<()> {
     use tokio::net::TcpListener;
     use tokio::io::{AsyncReadExt, AsyncWriteExt};
 
     let mut ctx = SslCtx::new()?;
     ctx.load_certificate_chain(c"cert.pem", c"key.pem")?;
+    if let Some(file) = keylog { ctx.set_keylog_file(file)?; }
 
     let sock = TcpListener::bind(addr).await?;
 
     let (conn, addr) = sock.accept().await?;
     eprintln!("[*] Connection from {addr}");
     conn.set_nodelay(true)?;
-    let mut ssl = AsyncSsl::new(&ctx, conn)?;
-    ssl.accept().await?;
+    let mut ssl = TlsAcceptor::from_ctx(ctx).accept_async(conn).await?;
 
     // Read stdin
     let mut buf = vec![];
@@ -0,0 +1,61 @@
+use core::ffi::c_long;
+
+use crate::sys;
+use crate::ErrorStack;
+use crate::bio::MemBioSlice;
+
+/// Public or private key
+#[derive(Debug)]
+pub struct PKey(pub(crate) *mut sys::EVP_PKEY);
+
+// Keys are reference counted and immutable after parsing
+unsafe impl Send for PKey {}
+unsafe impl Sync for PKey {}
+
+impl PKey {
+    /// Parses a PEM-encoded private key (PKCS#8 or traditional format)
+    #[doc(alias = "PEM_read_bio_PrivateKey")]
+    pub fn private_key_from_pem(pem: &[u8]) -> Result<PKey, ErrorStack> {
+        let bio = MemBioSlice::new(pem)?;
+        let ptr = unsafe { sys::PEM_read_bio_PrivateKey(bio.as_ptr(), core::ptr::null_mut(), core::ptr::null(), core::ptr::null_mut()) };
+        if ptr.is_null() { return Err(ErrorStack::get()); }
+        Ok(PKey(ptr))
+    }
+
+    /// Parses a DER-encoded private key, detecting its type automatically
+    #[doc(alias = "d2i_AutoPrivateKey")]
+    pub fn private_key_from_der(der: &[u8]) -> Result<PKey, ErrorStack> {
+        let len = c_long::try_from(der.len()).expect("buffer too large");
+        let mut p = der.as_ptr();
+        let ptr = unsafe { sys::d2i_AutoPrivateKey(core::ptr::null_mut(), &mut p, len) };
+        if ptr.is_null() { return Err(ErrorStack::get()); }
+        Ok(PKey(ptr))
+    }
+
+    /// Encodes the public key as DER `SubjectPublicKeyInfo`, e.g. for key pinning
+    #[doc(alias = "i2d_PUBKEY")]
+    pub fn public_key_to_der(&self) -> Result<Vec<u8>, ErrorStack> {
+        let len = unsafe { sys::i2d_PUBKEY(self.0, core::ptr::null_mut()) };
+        if len <= 0 { return Err(ErrorStack::get()); }
+
+        let mut buf = vec![0u8; len as usize];
+        let mut p = buf.as_mut_ptr();
+        let len = unsafe { sys::i2d_PUBKEY(self.0, &mut p) };
+        if len <= 0 { return Err(ErrorStack::get()); }
+        buf.truncate(len as usize);
+        Ok(buf)
+    }
+}
+
+impl Clone for PKey {
+    fn clone(&self) -> PKey {
+        unsafe { sys::EVP_PKEY_up_ref(self.0) };
+        PKey(self.0)
+    }
+}
+
+impl Drop for PKey {
+    fn drop(&mut self) {
+        unsafe { sys::EVP_PKEY_free(self.0) };
+    }
+}
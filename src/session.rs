@@ -0,0 +1,341 @@
+use core::fmt;
+use core::ffi::{CStr, c_int, c_long, c_void};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, RwLock};
+
+use crate::{sys, ex_data};
+use crate::{ErrorStack, TlsVersion};
+use crate::bio::{MemBio, MemBioSlice};
+use crate::ssl::catch_callback;
+use crate::sni::original_ctx;
+
+/// TLS session, which can be used to resume a connection with an abbreviated handshake
+///
+/// Obtained from [`Ssl::session`](crate::Ssl::session) after the handshake,
+/// or deserialized from the previously saved encoding
+#[derive(Debug)]
+pub struct SslSession(pub(crate) *mut sys::SSL_SESSION);
+
+// Sessions are reference counted and not modified after the handshake
+unsafe impl Send for SslSession {}
+unsafe impl Sync for SslSession {}
+
+impl SslSession {
+    /// Parses a DER-encoded session
+    #[doc(alias = "d2i_SSL_SESSION")]
+    pub fn from_der(der: &[u8]) -> Result<SslSession, ErrorStack> {
+        let len = c_long::try_from(der.len()).expect("buffer too large");
+        let mut p = der.as_ptr();
+        let ptr = unsafe { sys::d2i_SSL_SESSION(core::ptr::null_mut(), &mut p, len) };
+        if ptr.is_null() { return Err(ErrorStack::get()); }
+        Ok(SslSession(ptr))
+    }
+
+    /// Parses a PEM-encoded session
+    #[doc(alias = "PEM_read_bio_SSL_SESSION")]
+    pub fn from_pem(pem: &[u8]) -> Result<SslSession, ErrorStack> {
+        let bio = MemBioSlice::new(pem)?;
+        let ptr = unsafe { sys::PEM_read_bio_SSL_SESSION(bio.as_ptr(), core::ptr::null_mut(), core::ptr::null(), core::ptr::null_mut()) };
+        if ptr.is_null() { return Err(ErrorStack::get()); }
+        Ok(SslSession(ptr))
+    }
+
+    /// Encodes the session as DER
+    ///
+    /// The encoding contains the master secret, store it accordingly
+    #[doc(alias = "i2d_SSL_SESSION")]
+    pub fn to_der(&self) -> Result<Vec<u8>, ErrorStack> {
+        let len = unsafe { sys::i2d_SSL_SESSION(self.0, core::ptr::null_mut()) };
+        if len <= 0 { return Err(ErrorStack::get()); }
+
+        let mut buf = vec![0u8; len as usize];
+        let mut p = buf.as_mut_ptr();
+        let len = unsafe { sys::i2d_SSL_SESSION(self.0, &mut p) };
+        if len <= 0 { return Err(ErrorStack::get()); }
+        buf.truncate(len as usize);
+        Ok(buf)
+    }
+
+    /// Encodes the session as PEM
+    ///
+    /// The encoding contains the master secret, store it accordingly
+    #[doc(alias = "PEM_write_bio_SSL_SESSION")]
+    pub fn to_pem(&self) -> Result<Vec<u8>, ErrorStack> {
+        let bio = MemBio::new()?;
+        let ret = unsafe { sys::PEM_write_bio_SSL_SESSION(bio.as_ptr(), self.0) };
+        if ret == 0 { return Err(ErrorStack::get()); }
+        Ok(bio.get_buf().to_vec())
+    }
+
+    /// Returns true if the session can be used for resumption
+    #[doc(alias = "SSL_SESSION_is_resumable")]
+    pub fn is_resumable(&self) -> bool {
+        unsafe { sys::SSL_SESSION_is_resumable(self.0) == 1 }
+    }
+
+    /// Returns the protocol version of the session
+    #[doc(alias = "SSL_SESSION_get_protocol_version")]
+    pub fn protocol_version(&self) -> Option<TlsVersion> {
+        TlsVersion::from_raw(unsafe { sys::SSL_SESSION_get_protocol_version(self.0) } as c_long)
+    }
+}
+
+impl Clone for SslSession {
+    fn clone(&self) -> SslSession {
+        unsafe { sys::SSL_SESSION_up_ref(self.0) };
+        SslSession(self.0)
+    }
+}
+
+impl Drop for SslSession {
+    fn drop(&mut self) {
+        unsafe { sys::SSL_SESSION_free(self.0) };
+    }
+}
+
+/// Client-side sessions of a context, keyed by host
+///
+/// Ordered from least to most recently used, the front is evicted when full
+pub(crate) struct ClientSessionCache {
+    sessions: Mutex<VecDeque<(String, SslSession)>>,
+    capacity: usize,
+}
+
+/// Cache key of the connection, set together with the hostname
+struct SessionKey(String);
+
+impl ClientSessionCache {
+    pub(crate) fn new(capacity: usize) -> ClientSessionCache {
+        ClientSessionCache { sessions: Mutex::new(VecDeque::new()), capacity }
+    }
+
+    fn insert(&self, key: &str, session: SslSession) {
+        if self.capacity == 0 { return; }
+        let mut sessions = self.sessions.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(pos) = sessions.iter().position(|(k, _)| k == key) {
+            sessions.remove(pos);
+        } else if sessions.len() >= self.capacity {
+            sessions.pop_front();
+        }
+        sessions.push_back((key.to_string(), session));
+    }
+
+    fn take(&self, key: &str) -> Option<SslSession> {
+        let mut sessions = self.sessions.lock().unwrap_or_else(|e| e.into_inner());
+        let pos = sessions.iter().position(|(k, _)| k == key)?;
+        let (key, session) = sessions.remove(pos)?;
+        // TLS 1.3 tickets should not be reused, the server sends new ones after resumption
+        if session.protocol_version() != Some(TlsVersion::Tls1_3) {
+            sessions.push_back((key, session.clone()));
+        }
+        Some(session)
+    }
+}
+
+/// Remembers the cache key of the connection, and resumes the cached session if there is one
+pub(crate) unsafe fn resume_cached_session(ssl: *mut sys::SSL, key: &CStr) {
+    let ctx = unsafe { sys::SSL_get_SSL_CTX(ssl) };
+    let Some(cache) = (unsafe { ex_data::ctx_get::<ClientSessionCache>(ctx) }) else { return };
+    let key = key.to_string_lossy().into_owned();
+
+    if let Some(session) = cache.take(&key) {
+        // Failure only means a full handshake
+        if unsafe { sys::SSL_set_session(ssl, session.0) } == 0 {
+            ErrorStack::clear();
+        }
+    }
+    unsafe { ex_data::ssl_set(ssl, SessionKey(key)) };
+}
+
+pub(crate) unsafe extern "C" fn new_session_trampoline(ssl: *mut sys::SSL, session: *mut sys::SSL_SESSION) -> c_int {
+    let ctx = unsafe { sys::SSL_get_SSL_CTX(ssl) };
+    let Some(cache) = (unsafe { ex_data::ctx_get::<ClientSessionCache>(ctx) }) else { return 0 };
+    let Some(key) = (unsafe { ex_data::ssl_get::<SessionKey>(ssl) }) else { return 0 };
+
+    // Returning 1 means we took ownership of the reference, the session is dropped even on panic
+    let session = SslSession(session);
+    unsafe { catch_callback(ssl, || cache.insert(&key.0, session)) };
+    1
+}
+
+/// Session cache flags for [`SslCtx::set_session_cache_mode`](crate::SslCtx::set_session_cache_mode), combined with `|`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SessionCacheMode(c_long);
+
+impl SessionCacheMode {
+    /// No session caching
+    pub const OFF: SessionCacheMode = SessionCacheMode(sys::SSL_SESS_CACHE_OFF);
+    /// Cache client sessions
+    pub const CLIENT: SessionCacheMode = SessionCacheMode(sys::SSL_SESS_CACHE_CLIENT);
+    /// Cache server sessions, the default for servers
+    pub const SERVER: SessionCacheMode = SessionCacheMode(sys::SSL_SESS_CACHE_SERVER);
+    /// Both `CLIENT` and `SERVER`
+    pub const BOTH: SessionCacheMode = SessionCacheMode(sys::SSL_SESS_CACHE_CLIENT | sys::SSL_SESS_CACHE_SERVER);
+    /// Do not flush expired sessions automatically every 255 connections
+    pub const NO_AUTO_CLEAR: SessionCacheMode = SessionCacheMode(sys::SSL_SESS_CACHE_NO_AUTO_CLEAR);
+    /// Do not look up sessions in the internal cache
+    pub const NO_INTERNAL_LOOKUP: SessionCacheMode = SessionCacheMode(sys::SSL_SESS_CACHE_NO_INTERNAL_LOOKUP);
+    /// Do not store sessions in the internal cache
+    pub const NO_INTERNAL_STORE: SessionCacheMode = SessionCacheMode(sys::SSL_SESS_CACHE_NO_INTERNAL_STORE);
+    /// Both `NO_INTERNAL_LOOKUP` and `NO_INTERNAL_STORE`
+    pub const NO_INTERNAL: SessionCacheMode = SessionCacheMode(sys::SSL_SESS_CACHE_NO_INTERNAL_LOOKUP | sys::SSL_SESS_CACHE_NO_INTERNAL_STORE);
+
+    /// Returns true if all flags from `other` are set
+    pub fn contains(self, other: SessionCacheMode) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub(crate) fn from_bits(bits: c_long) -> SessionCacheMode {
+        SessionCacheMode(bits)
+    }
+
+    pub(crate) fn bits(self) -> c_long {
+        self.0
+    }
+}
+
+impl core::ops::BitOr for SessionCacheMode {
+    type Output = SessionCacheMode;
+    fn bitor(self, rhs: SessionCacheMode) -> SessionCacheMode {
+        SessionCacheMode(self.0 | rhs.0)
+    }
+}
+
+impl core::ops::BitOrAssign for SessionCacheMode {
+    fn bitor_assign(&mut self, rhs: SessionCacheMode) {
+        self.0 |= rhs.0;
+    }
+}
+
+/// Session ticket encryption key
+///
+/// Servers sharing the same key can resume each other's sessions
+#[derive(Clone, PartialEq, Eq)]
+pub struct TicketKey {
+    name: [u8; 16],
+    hmac_key: [u8; 32],
+    aes_key: [u8; 32],
+}
+
+impl TicketKey {
+    /// Constructs the key from its parts. The name identifies the key and is sent in clear with the ticket
+    pub fn new(name: [u8; 16], hmac_key: [u8; 32], aes_key: [u8; 32]) -> TicketKey {
+        TicketKey { name, hmac_key, aes_key }
+    }
+
+    /// Constructs the key from 80 bytes: name, HMAC key and AES key, the layout used by OpenSSL and nginx
+    pub fn from_bytes(bytes: &[u8; 80]) -> TicketKey {
+        let (name, rest) = bytes.split_at(16);
+        let (hmac_key, aes_key) = rest.split_at(32);
+        TicketKey {
+            name: name.try_into().unwrap(),
+            hmac_key: hmac_key.try_into().unwrap(),
+            aes_key: aes_key.try_into().unwrap(),
+        }
+    }
+
+    /// Generates a random key
+    #[doc(alias = "RAND_bytes")]
+    pub fn generate() -> Result<TicketKey, ErrorStack> {
+        let mut bytes = [0u8; 80];
+        let ret = unsafe { sys::RAND_bytes(bytes.as_mut_ptr(), bytes.len() as c_int) };
+        if ret != 1 { return Err(ErrorStack::get()); }
+        Ok(TicketKey::from_bytes(&bytes))
+    }
+
+    /// Returns 80 bytes in the layout of [`TicketKey::from_bytes`], e.g. to distribute the key to other servers
+    pub fn to_bytes(&self) -> [u8; 80] {
+        let mut bytes = [0u8; 80];
+        bytes[..16].copy_from_slice(&self.name);
+        bytes[16..48].copy_from_slice(&self.hmac_key);
+        bytes[48..].copy_from_slice(&self.aes_key);
+        bytes
+    }
+
+    /// Public name of the key
+    pub fn name(&self) -> &[u8; 16] {
+        &self.name
+    }
+}
+
+impl fmt::Debug for TicketKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Don't leak secrets into logs
+        f.debug_struct("TicketKey").field("name", &self.name).finish_non_exhaustive()
+    }
+}
+
+/// Rotating set of session ticket keys for [`SslCtx::set_ticket_keys`](crate::SslCtx::set_ticket_keys)
+///
+/// New tickets are encrypted with the newest key, older keys are only used to decrypt tickets issued before rotation.
+/// Clients resuming with an older key get a fresh ticket
+#[derive(Debug)]
+pub struct TicketKeys {
+    keys: RwLock<Vec<TicketKey>>,
+    retain: usize,
+}
+
+impl TicketKeys {
+    /// Constructs the set with a single key, keeping up to `retain` keys after rotations
+    ///
+    /// # Panics
+    /// If `retain` is zero
+    pub fn new(key: TicketKey, retain: usize) -> TicketKeys {
+        assert!(retain > 0, "at least one ticket key must be retained");
+        TicketKeys { keys: RwLock::new(vec![key]), retain }
+    }
+
+    /// Makes `key` the newest key, forgetting the oldest one if there are too many
+    pub fn rotate(&self, key: TicketKey) {
+        let mut keys = self.keys.write().unwrap_or_else(|e| e.into_inner());
+        keys.insert(0, key);
+        keys.truncate(self.retain);
+    }
+
+    /// Returns the keys, newest first
+    pub fn keys(&self) -> Vec<TicketKey> {
+        self.keys.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+}
+
+pub(crate) struct TicketKeysHolder(pub(crate) Arc<TicketKeys>);
+
+pub(crate) unsafe extern "C" fn ticket_key_trampoline(ssl: *mut sys::SSL, key_name: *mut u8, iv: *mut u8, cctx: *mut sys::EVP_CIPHER_CTX, hctx: *mut sys::EVP_MAC_CTX, enc: c_int) -> c_int {
+    // OpenSSL calls the callback of the original context after an SNI switch, so the keys come from there too
+    let ctx = unsafe { original_ctx(ssl) };
+    let Some(holder) = (unsafe { ex_data::ctx_get::<TicketKeysHolder>(ctx) }) else { return -1 };
+    let keys = holder.0.keys.read().unwrap_or_else(|e| e.into_inner());
+    let key_name = unsafe { &mut *(key_name as *mut [u8; 16]) };
+    let iv = unsafe { &mut *(iv as *mut [u8; sys::EVP_MAX_IV_LENGTH]) };
+
+    if enc == 1 {
+        let Some(key) = keys.first() else { return -1 };
+        *key_name = key.name;
+        if unsafe { sys::RAND_bytes(iv.as_mut_ptr(), iv.len() as c_int) } != 1 { return -1; }
+        if unsafe { set_hmac_key(hctx, key) } != 1 { return -1; }
+        let ret = unsafe { sys::EVP_EncryptInit_ex(cctx, sys::EVP_aes_256_cbc(), core::ptr::null_mut(), key.aes_key.as_ptr(), iv.as_ptr()) };
+        if ret != 1 { return -1; }
+        1
+    } else {
+        // Unknown key means a full handshake
+        let Some(pos) = keys.iter().position(|k| k.name == *key_name) else { return 0 };
+        let key = &keys[pos];
+        if unsafe { set_hmac_key(hctx, key) } != 1 { return -1; }
+        let ret = unsafe { sys::EVP_DecryptInit_ex(cctx, sys::EVP_aes_256_cbc(), core::ptr::null_mut(), key.aes_key.as_ptr(), iv.as_ptr()) };
+        if ret != 1 { return -1; }
+        // 2 asks OpenSSL to issue a new ticket with the newest key. TLS 1.3 tickets are single use,
+        // and OpenSSL sends no ticket after resumption unless asked to
+        let tls13 = TlsVersion::from_raw(unsafe { sys::SSL_version(ssl) } as c_long) == Some(TlsVersion::Tls1_3);
+        if pos == 0 && !tls13 { 1 } else { 2 }
+    }
+}
+
+unsafe fn set_hmac_key(hctx: *mut sys::EVP_MAC_CTX, key: &TicketKey) -> c_int {
+    let mut digest = *b"SHA256\0";
+    let params = unsafe { [
+        sys::OSSL_PARAM_construct_octet_string(c"key".as_ptr(), key.hmac_key.as_ptr() as *mut c_void, key.hmac_key.len()),
+        sys::OSSL_PARAM_construct_utf8_string(c"digest".as_ptr(), digest.as_mut_ptr() as *mut _, 0),
+        sys::OSSL_PARAM_construct_end(),
+    ] };
+    unsafe { sys::EVP_MAC_CTX_set_params(hctx, params.as_ptr()) }
+}
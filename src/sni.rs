@@ -0,0 +1,124 @@
+use core::ffi::{c_int, c_void};
+use core::marker::PhantomData;
+
+use crate::{sys, ex_data};
+use crate::info::str_from_ptr;
+use crate::ssl::catch_callback;
+use crate::{SslCtx, ErrorStack, X509Ref, PKey};
+
+type ServernameFn = dyn Fn(&mut SniRequest) -> bool + Send + Sync;
+pub(crate) struct ServernameCallback(Box<ServernameFn>);
+
+/// Handshake in progress, passed to the server name callback
+///
+/// Allows to switch the context or the certificate according to the name requested by the client
+pub struct SniRequest<'a> {
+    ssl: *mut sys::SSL,
+    _marker: PhantomData<&'a mut sys::SSL>,
+}
+
+impl SniRequest<'_> {
+    /// Returns the server name sent by the client, if any
+    #[doc(alias = "SSL_get_servername")]
+    pub fn servername(&self) -> Option<&str> {
+        unsafe { str_from_ptr(sys::SSL_get_servername(self.ssl, sys::TLSEXT_NAMETYPE_host_name as c_int)) }
+    }
+
+    /// Switches the connection to another context, including its certificate and key
+    ///
+    /// The trust store, ALPN, keylog and info callbacks of the new context are used for the rest of the handshake.
+    /// The verify mode and callback, the message callback, the session cache and ticket keys
+    /// stay those of the context the connection was created from
+    #[doc(alias = "SSL_set_SSL_CTX")]
+    pub fn set_ctx(&mut self, ctx: &SslCtx) -> Result<(), ErrorStack> {
+        unsafe {
+            if ex_data::ssl_get::<OriginalCtx>(self.ssl).is_none() {
+                ex_data::ssl_set(self.ssl, OriginalCtx(sys::SSL_get_SSL_CTX(self.ssl)));
+            }
+        }
+        ErrorStack::clear();
+        let ptr = unsafe { sys::SSL_set_SSL_CTX(self.ssl, ctx.0) };
+        if ptr.is_null() { return Err(ErrorStack::get()); }
+        Ok(())
+    }
+
+    /// Sets the certificate presented to the client
+    #[doc(alias = "SSL_use_certificate")]
+    pub fn use_certificate(&mut self, cert: &X509Ref) -> Result<(), ErrorStack> {
+        ErrorStack::clear();
+        let ret = unsafe { sys::SSL_use_certificate(self.ssl, cert.as_ptr()) };
+        if ret == 0 { return Err(ErrorStack::get()); }
+        /* success == 1 */ Ok(())
+    }
+
+    /// Appends an intermediate certificate to the chain sent to the client
+    #[doc(alias = "SSL_add1_chain_cert")]
+    pub fn add_chain_certificate(&mut self, cert: &X509Ref) -> Result<(), ErrorStack> {
+        ErrorStack::clear();
+        let ret = unsafe { sys::SSL_add1_chain_cert(self.ssl, cert.as_ptr()) };
+        if ret == 0 { return Err(ErrorStack::get()); }
+        /* success == 1 */ Ok(())
+    }
+
+    /// Sets the private key matching the certificate
+    #[doc(alias = "SSL_use_PrivateKey", alias = "SSL_check_private_key")]
+    pub fn use_private_key(&mut self, key: &PKey) -> Result<(), ErrorStack> {
+        ErrorStack::clear();
+        let ret = unsafe { sys::SSL_use_PrivateKey(self.ssl, key.0) };
+        if ret == 0 { return Err(ErrorStack::get()); }
+
+        let ret = unsafe { sys::SSL_check_private_key(self.ssl) };
+        if ret == 0 { return Err(ErrorStack::get()); }
+
+        Ok(())
+    }
+}
+
+impl core::fmt::Debug for SniRequest<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("SniRequest").field("servername", &self.servername()).finish()
+    }
+}
+
+/// Context the SSL object was created from, recorded before switching to another one
+///
+/// The SSL object holds a reference to it until it is freed
+struct OriginalCtx(*mut sys::SSL_CTX);
+
+/// Returns the context the SSL object was created from, even after [`SniRequest::set_ctx`]
+///
+/// OpenSSL copies some settings into the SSL object or keeps using that context for them,
+/// e.g. the verify callback and ticket keys, so their closures must be looked up there
+pub(crate) unsafe fn original_ctx(ssl: *const sys::SSL) -> *mut sys::SSL_CTX {
+    match unsafe { ex_data::ssl_get::<OriginalCtx>(ssl) } {
+        Some(ctx) => ctx.0,
+        None => unsafe { sys::SSL_get_SSL_CTX(ssl) },
+    }
+}
+
+impl ServernameCallback {
+    pub(crate) fn new<F>(callback: F) -> ServernameCallback
+    where F: Fn(&mut SniRequest) -> bool + Send + Sync + 'static {
+        ServernameCallback(Box::new(callback))
+    }
+}
+
+pub(crate) unsafe extern "C" fn servername_trampoline(ssl: *mut sys::SSL, al: *mut c_int, _arg: *mut c_void) -> c_int {
+    let ctx = unsafe { sys::SSL_get_SSL_CTX(ssl) };
+    let Some(callback) = (unsafe { ex_data::ctx_get::<ServernameCallback>(ctx) }) else {
+        return sys::SSL_TLSEXT_ERR_OK;
+    };
+
+    let mut request = SniRequest { ssl, _marker: PhantomData };
+    match unsafe { catch_callback(ssl, || (callback.0)(&mut request)) } {
+        Some(true) => sys::SSL_TLSEXT_ERR_OK,
+        Some(false) => {
+            unsafe { *al = sys::SSL_AD_UNRECOGNIZED_NAME };
+            sys::SSL_TLSEXT_ERR_ALERT_FATAL
+        },
+        None => {
+            unsafe { *al = sys::SSL_AD_INTERNAL_ERROR };
+            sys::SSL_TLSEXT_ERR_ALERT_FATAL
+        },
+    }
+}
@@ -1,13 +1,22 @@
-use core::ffi::{CStr, c_int};
+use core::ffi::{CStr, c_int, c_long};
+use core::net::IpAddr;
+use std::ffi::CString;
+use std::any::Any;
 use std::io::{self, Read, Write};
+use std::panic::{self, AssertUnwindSafe};
 
 #[cfg(windows)]
 use std::os::windows::AsRawHandle;
 #[cfg(unix)]
 use std::os::fd::AsRawFd;
 
-use crate::sys;
-use crate::{SslCtx, ErrorStack, SslError};
+use crate::{sys, ex_data};
+use crate::ctx::{VerifyCallback, ssl_verify_trampoline};
+use crate::x509::{stack_refs, name_stack_refs};
+use crate::info::str_from_ptr;
+use crate::{SslCtx, ErrorStack, SslError, VerifyMode, X509, X509Ref, X509NameRef, X509StoreContextRef, X509VerifyError, PKey};
+use crate::{TlsVersion, SslCipherRef, SignatureAlgorithm, SslSession, VerifyError, PeerAlert, reason};
+use crate::session::resume_cached_session;
 
 /// Main SSL object
 ///
@@ -26,7 +35,7 @@ use crate::{SslCtx, ErrorStack, SslError};
 /// // Now call ssl.read(), ssl.write()
 ///
 /// // After you are done, close the connection
-/// ssl.shutdown()?;
+/// ssl.send_close_notify()?;
 /// # */
 /// # Ok(())
 /// # }
@@ -34,6 +43,65 @@ use crate::{SslCtx, ErrorStack, SslError};
 #[derive(Debug)]
 pub struct Ssl(*mut sys::SSL);
 
+/// Panic of a user callback, resumed after the SSL call that invoked it
+struct CallbackPanic(Box<dyn Any + Send>);
+
+/// Runs a user callback invoked by OpenSSL, returning `None` if it panicked
+///
+/// Unwinding into C is an abort, so the panic is stored and resumed by the [`Ssl`] method that called into OpenSSL,
+/// the same way `SslStream` does for its stream. The caller should fail the handshake on `None`
+pub(crate) unsafe fn catch_callback<T>(ssl: *const sys::SSL, f: impl FnOnce() -> T) -> Option<T> {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(ret) => Some(ret),
+        Err(payload) => {
+            let ssl = ssl as *mut sys::SSL;
+            // The first panic is the interesting one
+            if unsafe { ex_data::ssl_get::<CallbackPanic>(ssl) }.is_none() {
+                unsafe { ex_data::ssl_set(ssl, CallbackPanic(payload)) };
+            }
+            None
+        }
+    }
+}
+
+/// Marks the session after a fatal error
+struct FatalError;
+
+/// Marks that `close_notify` was written, not only queued
+struct CloseNotifySent;
+
+/// Marks a session over in-memory buffers, where errno has nothing to do with the connection
+#[cfg(feature = "tokio")]
+struct MemBios;
+
+/// Which sides have sent `close_notify`, returned by [`Ssl::shutdown_state`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShutdownState(c_int);
+
+impl ShutdownState {
+    /// We have sent `close_notify`
+    pub const SENT: ShutdownState = ShutdownState(sys::SSL_SENT_SHUTDOWN);
+    /// The peer has sent `close_notify`
+    pub const RECEIVED: ShutdownState = ShutdownState(sys::SSL_RECEIVED_SHUTDOWN);
+
+    /// Returns true if all flags from `other` are set
+    pub fn contains(self, other: ShutdownState) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Returns true if neither side has closed the connection
+    pub fn is_open(self) -> bool {
+        self.0 & (sys::SSL_SENT_SHUTDOWN | sys::SSL_RECEIVED_SHUTDOWN) == 0
+    }
+}
+
+impl core::ops::BitOr for ShutdownState {
+    type Output = ShutdownState;
+    fn bitor(self, rhs: ShutdownState) -> ShutdownState {
+        ShutdownState(self.0 | rhs.0)
+    }
+}
+
 // All non-reentrant methods take &mut ref
 unsafe impl Send for Ssl {}
 unsafe impl Sync for Ssl {}
@@ -42,20 +110,202 @@ impl Ssl {
     /// Constructs a new SSL object from the given context
     #[doc(alias = "SSL_new")]
     pub fn new(ctx: &SslCtx) -> Result<Ssl, ErrorStack> {
+        ErrorStack::clear();
         let ptr = unsafe { sys::SSL_new(ctx.0) };
         if ptr.is_null() { return Err(ErrorStack::get()); }
         Ok(Ssl(ptr))
     }
 
+    pub(crate) fn as_ptr(&self) -> *mut sys::SSL {
+        self.0
+    }
+
     /// Sets SNI and hostname for verification
+    ///
+    /// IP addresses are passed to [`Ssl::set_verify_ip`] instead, since SNI can't contain them.
+    /// If the client session cache is enabled, also resumes the session cached for this host
     #[doc(alias = "SSL_set1_host", alias = "SSL_set_tlsext_host_name")]
     pub fn set_hostname(&mut self, hostname: &CStr) -> Result<(), ErrorStack> {
+        ErrorStack::clear();
+        if let Some(ip) = hostname.to_str().ok().and_then(|h| h.parse::<IpAddr>().ok()) {
+            return self.set_verify_ip(ip);
+        }
+
         let ret = unsafe { sys::SSL_set1_host(self.0, hostname.as_ptr()) };
         if ret == 0 { return Err(ErrorStack::get()); }
 
         let ret = unsafe { sys::SSL_set_tlsext_host_name(self.0, hostname.as_ptr()) };
         if ret == 0 { return Err(ErrorStack::get()); }
 
+        unsafe { resume_cached_session(self.0, hostname) };
+        Ok(())
+    }
+
+    /// Sets the IP address expected in the peer certificate, without sending SNI
+    ///
+    /// If the client session cache is enabled, also resumes the session cached for this address
+    #[doc(alias = "X509_VERIFY_PARAM_set1_ip")]
+    pub fn set_verify_ip(&mut self, ip: IpAddr) -> Result<(), ErrorStack> {
+        ErrorStack::clear();
+        let octets = match ip {
+            IpAddr::V4(ip) => ip.octets().to_vec(),
+            IpAddr::V6(ip) => ip.octets().to_vec(),
+        };
+        let param = unsafe { sys::SSL_get0_param(self.0) };
+        // Only one of host and IP is checked by OpenSSL
+        let ret = unsafe { sys::X509_VERIFY_PARAM_set1_host(param, core::ptr::null(), 0) };
+        if ret == 0 { return Err(ErrorStack::get()); }
+        let ret = unsafe { sys::X509_VERIFY_PARAM_set1_ip(param, octets.as_ptr(), octets.len()) };
+        if ret == 0 { return Err(ErrorStack::get()); }
+
+        let key = CString::new(ip.to_string()).unwrap();
+        unsafe { resume_cached_session(self.0, &key) };
+        Ok(())
+    }
+
+    /// Returns the current session, which can be saved for resumption
+    ///
+    /// With TLS 1.3, the resumable session is only available after the server sends a ticket,
+    /// which usually happens after the handshake, with the first data read
+    #[doc(alias = "SSL_get1_session")]
+    pub fn session(&self) -> Option<SslSession> {
+        let ptr = unsafe { sys::SSL_get1_session(self.0) };
+        if ptr.is_null() { return None; }
+        Some(SslSession(ptr))
+    }
+
+    /// Sets the session to resume, must be called before [`Ssl::connect`]
+    ///
+    /// The server falls back to a full handshake if it can't resume the session
+    #[doc(alias = "SSL_set_session")]
+    pub fn set_session(&mut self, session: &SslSession) -> Result<(), ErrorStack> {
+        ErrorStack::clear();
+        let ret = unsafe { sys::SSL_set_session(self.0, session.0) };
+        if ret == 0 { return Err(ErrorStack::get()); }
+        /* success == 1 */ Ok(())
+    }
+
+    /// Overrides the minimal protocol version of the context for this connection
+    #[doc(alias = "SSL_set_min_proto_version")]
+    pub fn set_min_version(&mut self, ver: Option<TlsVersion>) -> Result<(), ErrorStack> {
+        ErrorStack::clear();
+        let ret = unsafe { sys::SSL_set_min_proto_version(self.0, ver.map_or(0, TlsVersion::as_raw)) };
+        if ret == 0 { return Err(ErrorStack::get()); }
+        /* success == 1 */ Ok(())
+    }
+
+    /// Overrides the maximal protocol version of the context for this connection
+    #[doc(alias = "SSL_set_max_proto_version")]
+    pub fn set_max_version(&mut self, ver: Option<TlsVersion>) -> Result<(), ErrorStack> {
+        ErrorStack::clear();
+        let ret = unsafe { sys::SSL_set_max_proto_version(self.0, ver.map_or(0, TlsVersion::as_raw)) };
+        if ret == 0 { return Err(ErrorStack::get()); }
+        /* success == 1 */ Ok(())
+    }
+
+    /// Returns the minimal protocol version, or `None` if there is no bound
+    #[doc(alias = "SSL_get_min_proto_version")]
+    pub fn min_version(&self) -> Option<TlsVersion> {
+        TlsVersion::from_raw(unsafe { sys::SSL_get_min_proto_version(self.0) })
+    }
+
+    /// Returns the maximal protocol version, or `None` if there is no bound
+    #[doc(alias = "SSL_get_max_proto_version")]
+    pub fn max_version(&self) -> Option<TlsVersion> {
+        TlsVersion::from_raw(unsafe { sys::SSL_get_max_proto_version(self.0) })
+    }
+
+    /// Overrides TLS 1.3 cipher suites of the context for this connection
+    #[doc(alias = "SSL_set_ciphersuites")]
+    pub fn set_ciphersuites(&mut self, list: &CStr) -> Result<(), ErrorStack> {
+        ErrorStack::clear();
+        let ret = unsafe { sys::SSL_set_ciphersuites(self.0, list.as_ptr()) };
+        if ret == 0 { return Err(ErrorStack::get()); }
+        /* success == 1 */ Ok(())
+    }
+
+    /// Overrides key exchange groups of the context for this connection
+    #[doc(alias = "SSL_set1_groups_list")]
+    pub fn set_groups_list(&mut self, list: &CStr) -> Result<(), ErrorStack> {
+        ErrorStack::clear();
+        let ret = unsafe { sys::SSL_set1_groups_list(self.0, list.as_ptr()) };
+        if ret == 0 { return Err(ErrorStack::get()); }
+        /* success == 1 */ Ok(())
+    }
+
+    /// Overrides handshake signature algorithms of the context for this connection
+    #[doc(alias = "SSL_set1_sigalgs_list")]
+    pub fn set_sigalgs_list(&mut self, list: &CStr) -> Result<(), ErrorStack> {
+        ErrorStack::clear();
+        let ret = unsafe { sys::SSL_set1_sigalgs_list(self.0, list.as_ptr()) };
+        if ret == 0 { return Err(ErrorStack::get()); }
+        /* success == 1 */ Ok(())
+    }
+
+    /// Overrides client certificate signature algorithms of the context for this connection
+    #[doc(alias = "SSL_set1_client_sigalgs_list")]
+    pub fn set_client_sigalgs_list(&mut self, list: &CStr) -> Result<(), ErrorStack> {
+        ErrorStack::clear();
+        let ret = unsafe { sys::SSL_set1_client_sigalgs_list(self.0, list.as_ptr()) };
+        if ret == 0 { return Err(ErrorStack::get()); }
+        /* success == 1 */ Ok(())
+    }
+
+    /// Overrides certificate verification flags of the context for this connection
+    #[doc(alias = "SSL_set_verify")]
+    pub fn set_verify_mode(&mut self, mode: VerifyMode) {
+        unsafe {
+            let callback = sys::SSL_get_verify_callback(self.0);
+            sys::SSL_set_verify(self.0, mode.bits(), callback);
+        }
+    }
+
+    /// Overrides the verify callback of the context for this connection
+    ///
+    /// Check [`SslCtx::set_verify_callback`] for details
+    #[doc(alias = "SSL_set_verify")]
+    pub fn set_verify_callback<F>(&mut self, callback: F)
+    where F: Fn(bool, &X509StoreContextRef) -> bool + Send + Sync + 'static {
+        unsafe {
+            ex_data::ssl_set(self.0, VerifyCallback::new(callback));
+            sys::SSL_set_verify(self.0, sys::SSL_get_verify_mode(self.0), Some(ssl_verify_trampoline));
+        }
+    }
+
+    /// Returns certificate verification flags of this connection
+    #[doc(alias = "SSL_get_verify_mode")]
+    pub fn verify_mode(&self) -> VerifyMode {
+        VerifyMode::from_bits(unsafe { sys::SSL_get_verify_mode(self.0) })
+    }
+
+    /// Sets the certificate presented to the peer, e.g. the client certificate for mutual TLS
+    #[doc(alias = "SSL_use_certificate")]
+    pub fn use_certificate(&mut self, cert: &X509Ref) -> Result<(), ErrorStack> {
+        ErrorStack::clear();
+        let ret = unsafe { sys::SSL_use_certificate(self.0, cert.as_ptr()) };
+        if ret == 0 { return Err(ErrorStack::get()); }
+        /* success == 1 */ Ok(())
+    }
+
+    /// Appends an intermediate certificate to the chain sent to the peer
+    #[doc(alias = "SSL_add1_chain_cert")]
+    pub fn add_chain_certificate(&mut self, cert: &X509Ref) -> Result<(), ErrorStack> {
+        ErrorStack::clear();
+        let ret = unsafe { sys::SSL_add1_chain_cert(self.0, cert.as_ptr()) };
+        if ret == 0 { return Err(ErrorStack::get()); }
+        /* success == 1 */ Ok(())
+    }
+
+    /// Sets the private key matching the certificate
+    #[doc(alias = "SSL_use_PrivateKey", alias = "SSL_check_private_key")]
+    pub fn use_private_key(&mut self, key: &PKey) -> Result<(), ErrorStack> {
+        ErrorStack::clear();
+        let ret = unsafe { sys::SSL_use_PrivateKey(self.0, key.0) };
+        if ret == 0 { return Err(ErrorStack::get()); }
+
+        let ret = unsafe { sys::SSL_check_private_key(self.0) };
+        if ret == 0 { return Err(ErrorStack::get()); }
+
         Ok(())
     }
 
@@ -63,6 +313,7 @@ impl Ssl {
     #[doc(alias = "SSL_set_fd")]
     #[cfg(windows)]
     pub fn set_fd(&mut self, fd: &impl AsRawHandle) -> Result<(), ErrorStack> {
+        ErrorStack::clear();
         let ret = unsafe { sys::SSL_set_fd(self.0, fd.as_raw_handle() as c_int) };
         if ret == 0 { return Err(ErrorStack::get()); }
         /* success == 1 */ Ok(())
@@ -72,29 +323,152 @@ impl Ssl {
     #[doc(alias = "SSL_set_fd")]
     #[cfg(unix)]
     pub fn set_fd(&mut self, fd: &impl AsRawFd) -> Result<(), ErrorStack> {
+        ErrorStack::clear();
         let ret = unsafe { sys::SSL_set_fd(self.0, fd.as_raw_fd()) };
         if ret == 0 { return Err(ErrorStack::get()); }
         /* success == 1 */ Ok(())
     }
 
+    /// Connects TLS to in-memory buffers, which are then drained and fed by the caller
+    #[cfg(feature = "tokio")]
+    pub(crate) fn set_mem_bios(&mut self) -> Result<(), ErrorStack> {
+        ErrorStack::clear();
+        let rbio = unsafe { sys::BIO_new(sys::BIO_s_mem()) };
+        if rbio.is_null() { return Err(ErrorStack::get()); }
+        let wbio = unsafe { sys::BIO_new(sys::BIO_s_mem()) };
+        if wbio.is_null() {
+            unsafe { sys::BIO_free(rbio) };
+            return Err(ErrorStack::get());
+        }
+        // Takes ownership of both
+        unsafe {
+            sys::SSL_set_bio(self.0, rbio, wbio);
+            ex_data::ssl_set(self.0, MemBios);
+        }
+        Ok(())
+    }
+
+    /// Moves encrypted data waiting to be sent into `buf`
+    #[cfg(feature = "tokio")]
+    pub(crate) fn take_output(&mut self, buf: &mut Vec<u8>) {
+        let wbio = unsafe { sys::SSL_get_wbio(self.0) };
+        let pending = unsafe { sys::BIO_pending(wbio) };
+        if pending <= 0 { return; }
+
+        let start = buf.len();
+        buf.resize(start + pending as usize, 0);
+        let ret = unsafe { sys::BIO_read(wbio, buf[start..].as_mut_ptr().cast(), pending) };
+        buf.truncate(start + ret.max(0) as usize);
+    }
+
+    /// Feeds data received from the peer, empty `data` marks the end of stream
+    #[cfg(feature = "tokio")]
+    pub(crate) fn feed_input(&mut self, data: &[u8]) -> Result<(), ErrorStack> {
+        ErrorStack::clear();
+        let rbio = unsafe { sys::SSL_get_rbio(self.0) };
+        if data.is_empty() {
+            // Reading an empty buffer now reports EOF instead of "retry later"
+            unsafe { sys::BIO_set_mem_eof_return(rbio, 0) };
+            return Ok(());
+        }
+
+        let len = c_int::try_from(data.len()).expect("buffer too large for BIO");
+        let ret = unsafe { sys::BIO_write(rbio, data.as_ptr().cast(), len) };
+        if ret != len { return Err(ErrorStack::get()); }
+        Ok(())
+    }
+
     /// Performs the SSL connection as a client
     #[doc(alias = "SSL_connect")]
     pub fn connect(&mut self) -> Result<(), SslError> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("tls_connect").entered();
+        self.connect_step()
+    }
+
+    /// [`Ssl::connect`] without a span, for callers that trace the whole handshake across calls
+    pub(crate) fn connect_step(&mut self) -> Result<(), SslError> {
+        ErrorStack::clear();
         let ret = unsafe { sys::SSL_connect(self.0) };
+        self.resume_panic();
         if ret == 1 { return Ok(()); }
         Err(self.make_error(ret))
     }
 
-    /// Gracefully closes the connection
+    /// Sends `close_notify` without waiting for the peer's one, same as [`Ssl::send_close_notify`]
+    ///
+    /// The peer's `close_notify` ends a later read with `Ok(0)`, [`Ssl::shutdown_bidirectional`] waits for it instead
     #[doc(alias = "SSL_shutdown")]
     pub fn shutdown(&mut self) -> Result<(), SslError> {
-        loop {
-            let ret = unsafe { sys::SSL_shutdown(self.0) };
-            match ret {
-                1 => return Ok(()),
-                0 => continue, // retry
-                _ => return Err(self.make_error(ret)),
-            }
+        self.send_close_notify()
+    }
+
+    /// Sends `close_notify`, telling the peer that we will not write anymore
+    ///
+    /// Does nothing if it was already sent. Reading is still possible until the peer closes its side
+    #[doc(alias = "SSL_shutdown")]
+    pub fn send_close_notify(&mut self) -> Result<(), SslError> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("tls_shutdown").entered();
+        self.close_notify_step()
+    }
+
+    /// [`Ssl::send_close_notify`] without a span
+    pub(crate) fn close_notify_step(&mut self) -> Result<(), SslError> {
+        // SSL_shutdown would wait for the peer's close_notify on the second call
+        if unsafe { ex_data::ssl_get::<CloseNotifySent>(self.0) }.is_some() { return Ok(()); }
+        ErrorStack::clear();
+        let ret = unsafe { sys::SSL_shutdown(self.0) };
+        self.resume_panic();
+        if ret < 0 { return Err(self.make_error(ret)); }
+        /* ret == 0 || ret == 1 */ unsafe { ex_data::ssl_set(self.0, CloseNotifySent) };
+        #[cfg(feature = "tracing")]
+        tracing::debug!("TLS close_notify sent");
+        Ok(())
+    }
+
+    /// Sends `close_notify` and waits for the peer's one, so that no data was truncated
+    ///
+    /// Fails if application data arrives first, the data can then be read and the shutdown retried.
+    /// On non-blocking sockets, fails with [`SslError::WantRead`] or [`SslError::WantWrite`] and has to be retried
+    pub fn shutdown_bidirectional(&mut self) -> Result<(), SslError> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("tls_shutdown").entered();
+        self.shutdown_bidirectional_step()
+    }
+
+    /// [`Ssl::shutdown_bidirectional`] without a span
+    pub(crate) fn shutdown_bidirectional_step(&mut self) -> Result<(), SslError> {
+        self.close_notify_step()?;
+        // SSL_shutdown fails when data arrives before close_notify, SSL_peek leaves it to be read
+        ErrorStack::clear();
+        let ret = unsafe { sys::SSL_peek(self.0, [0].as_mut_ptr(), 1) };
+        self.resume_panic();
+        if ret > 0 {
+            return Err(ErrorStack::custom("application data received before close_notify").into());
+        }
+        match self.make_error(ret) {
+            SslError::ZeroReturn => Ok(()),
+            err => Err(err),
+        }
+    }
+
+    /// Returns false on half-open or failed sessions, where there is nothing to close
+    pub(crate) fn can_send_close_notify(&self) -> bool {
+        let failed = unsafe { ex_data::ssl_get::<FatalError>(self.0) }.is_some();
+        self.is_handshake_finished() && !failed
+    }
+
+    /// Returns which sides have closed the connection with `close_notify`
+    #[doc(alias = "SSL_get_shutdown")]
+    pub fn shutdown_state(&self) -> ShutdownState {
+        ShutdownState(unsafe { sys::SSL_get_shutdown(self.0) })
+    }
+
+    /// Resumes the panic of a callback called during the last operation
+    fn resume_panic(&self) {
+        if let Some(CallbackPanic(payload)) = unsafe { ex_data::ssl_take(self.0) } {
+            panic::resume_unwind(payload);
         }
     }
 
@@ -102,24 +476,191 @@ impl Ssl {
         use sys::error::*;
 
         let code = unsafe { sys::SSL_get_error(self.0, ret) };
-        match code {
+        let err = match code {
             SSL_ERROR_ZERO_RETURN => SslError::ZeroReturn,
-            SSL_ERROR_SYSCALL => SslError::Syscall(io::Error::last_os_error()),
-            SSL_ERROR_SSL => SslError::Ssl(ErrorStack::get()),
+            SSL_ERROR_SYSCALL => self.syscall_error(),
+            SSL_ERROR_SSL => self.ssl_error(ErrorStack::get()),
             SSL_ERROR_WANT_READ => SslError::WantRead,
             SSL_ERROR_WANT_WRITE => SslError::WantWrite,
             _ => SslError::Other,
+        };
+        // OpenSSL forbids shutdown after these
+        if matches!(code, SSL_ERROR_SYSCALL | SSL_ERROR_SSL) {
+            unsafe { ex_data::ssl_set(self.0, FatalError) };
+        }
+        #[cfg(feature = "tracing")]
+        if !matches!(err, SslError::ZeroReturn | SslError::WantRead | SslError::WantWrite) {
+            tracing::debug!(error = %err, "TLS operation failed");
         }
+        err
+    }
+
+    /// I/O error of the transport. [`SslStream`](crate::SslStream) replaces it with the error of its stream
+    fn syscall_error(&self) -> SslError {
+        // Memory buffers only fail when the input has ended
+        #[cfg(feature = "tokio")]
+        if unsafe { ex_data::ssl_get::<MemBios>(self.0) }.is_some() {
+            return SslError::UnexpectedEof;
+        }
+        SslError::Syscall(io::Error::last_os_error())
+    }
+
+    /// Classifies a protocol error: truncation, alert from the peer or local verification failure
+    fn ssl_error(&self, stack: ErrorStack) -> SslError {
+        if stack.contains(reason::ERR_LIB_SSL, reason::SSL_R_UNEXPECTED_EOF_WHILE_READING) {
+            return SslError::UnexpectedEof;
+        }
+        if let Some(code) = stack.peer_alert() {
+            return SslError::PeerAlert(PeerAlert::new(code, stack));
+        }
+        if stack.contains(reason::ERR_LIB_SSL, reason::SSL_R_CERTIFICATE_VERIFY_FAILED)
+            && let Some(failure) = unsafe { ex_data::ssl_get::<VerifyError>(self.0) } {
+            return SslError::Verify(failure.with_stack(stack));
+        }
+        SslError::Ssl(stack)
     }
 
     /// Accepts the SSL connection as a server
     #[doc(alias = "SSL_accept")]
     pub fn accept(&mut self) -> Result<(), SslError> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("tls_accept").entered();
+        self.accept_step()
+    }
+
+    /// [`Ssl::accept`] without a span
+    pub(crate) fn accept_step(&mut self) -> Result<(), SslError> {
+        ErrorStack::clear();
         let ret = unsafe { sys::SSL_accept(self.0) };
+        self.resume_panic();
         if ret == 1 { return Ok(()); }
         /* ret <= 0 */ Err(self.make_error(ret))
     }
 
+    /// Continues the handshake started by [`Ssl::connect`] or [`Ssl::accept`]
+    #[doc(alias = "SSL_do_handshake")]
+    pub fn do_handshake(&mut self) -> Result<(), SslError> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("tls_handshake").entered();
+        ErrorStack::clear();
+        let ret = unsafe { sys::SSL_do_handshake(self.0) };
+        self.resume_panic();
+        if ret == 1 { return Ok(()); }
+        /* ret <= 0 */ Err(self.make_error(ret))
+    }
+
+    /// Returns true if the handshake has completed
+    #[doc(alias = "SSL_is_init_finished")]
+    pub fn is_handshake_finished(&self) -> bool {
+        unsafe { sys::SSL_is_init_finished(self.0) == 1 }
+    }
+
+    /// Returns the certificate presented by the peer, if any
+    #[doc(alias = "SSL_get1_peer_certificate")]
+    pub fn peer_certificate(&self) -> Option<X509> {
+        let ptr = unsafe { sys::SSL_get1_peer_certificate(self.0) };
+        if ptr.is_null() { return None; }
+        Some(X509(ptr))
+    }
+
+    /// Returns the certificate chain sent by the peer, as is
+    ///
+    /// On the client, it starts with the server certificate. On the server, it does not include the client certificate
+    #[doc(alias = "SSL_get_peer_cert_chain")]
+    pub fn peer_cert_chain(&self) -> Vec<&X509Ref> {
+        unsafe { stack_refs(sys::SSL_get_peer_cert_chain(self.0)) }
+    }
+
+    /// Returns the verified chain from the peer certificate up to the trusted root
+    ///
+    /// Empty if the peer was not verified
+    #[doc(alias = "SSL_get0_verified_chain")]
+    pub fn verified_chain(&self) -> Vec<&X509Ref> {
+        unsafe { stack_refs(sys::SSL_get0_verified_chain(self.0)) }
+    }
+
+    /// Returns the CA names the server advertised when requesting the client certificate
+    ///
+    /// On the server, returns the names it sends
+    #[doc(alias = "SSL_get_client_CA_list")]
+    pub fn client_ca_names(&self) -> Vec<&X509NameRef> {
+        unsafe { name_stack_refs(sys::SSL_get_client_CA_list(self.0)) }
+    }
+
+    /// Returns the result of peer certificate verification
+    ///
+    /// Also `Ok` if the peer did not present a certificate, check [`Ssl::peer_certificate`] for that
+    #[doc(alias = "SSL_get_verify_result")]
+    pub fn verify_result(&self) -> Result<(), X509VerifyError> {
+        let code = unsafe { sys::SSL_get_verify_result(self.0) };
+        match X509VerifyError::from_raw(code as c_int) {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the negotiated protocol version
+    ///
+    /// Before the handshake, returns the maximal version that will be offered
+    #[doc(alias = "SSL_version")]
+    pub fn version(&self) -> Option<TlsVersion> {
+        TlsVersion::from_raw(unsafe { sys::SSL_version(self.0) } as c_long)
+    }
+
+    /// Returns the negotiated cipher suite
+    #[doc(alias = "SSL_get_current_cipher")]
+    pub fn current_cipher(&self) -> Option<&SslCipherRef> {
+        let ptr = unsafe { sys::SSL_get_current_cipher(self.0) };
+        if ptr.is_null() { return None; }
+        Some(unsafe { SslCipherRef::from_ptr(ptr) })
+    }
+
+    /// Returns the name of the negotiated key exchange group, e.g. `X25519MLKEM768`
+    #[doc(alias = "SSL_get_negotiated_group", alias = "SSL_group_to_name")]
+    pub fn negotiated_group(&self) -> Option<&str> {
+        // OpenSSL crashes when there is no session yet
+        if unsafe { sys::SSL_get_session(self.0) }.is_null() { return None; }
+        let id = unsafe { sys::SSL_get_negotiated_group(self.0) };
+        if id <= 0 { return None; }
+        unsafe { str_from_ptr(sys::SSL_group_to_name(self.0, id)) }
+    }
+
+    /// Returns the algorithm the peer used to sign the handshake
+    #[doc(alias = "SSL_get_peer_signature_type_nid", alias = "SSL_get_peer_signature_nid")]
+    pub fn peer_signature_algorithm(&self) -> Option<SignatureAlgorithm> {
+        let mut sig = 0;
+        let ret = unsafe { sys::SSL_get_peer_signature_type_nid(self.0, &mut sig) };
+        if ret == 0 { return None; }
+
+        let mut digest = 0;
+        unsafe { sys::SSL_get_peer_signature_nid(self.0, &mut digest) };
+        SignatureAlgorithm::from_nids(sig, digest)
+    }
+
+    /// Returns true if the session was resumed instead of a full handshake
+    #[doc(alias = "SSL_session_reused")]
+    pub fn session_reused(&self) -> bool {
+        unsafe { sys::SSL_session_reused(self.0) == 1 }
+    }
+
+    /// Returns the server name sent via SNI
+    ///
+    /// On the server, this is the name requested by the client
+    #[doc(alias = "SSL_get_servername")]
+    pub fn servername(&self) -> Option<&str> {
+        unsafe { str_from_ptr(sys::SSL_get_servername(self.0, sys::TLSEXT_NAMETYPE_host_name as c_int)) }
+    }
+
+    /// Returns the protocol selected via ALPN, if any
+    #[doc(alias = "SSL_get0_alpn_selected")]
+    pub fn selected_alpn_protocol(&self) -> Option<&[u8]> {
+        let mut data = core::ptr::null();
+        let mut len = 0;
+        unsafe { sys::SSL_get0_alpn_selected(self.0, &mut data, &mut len) };
+        if data.is_null() || len == 0 { return None; }
+        Some(unsafe { core::slice::from_raw_parts(data, len as usize) })
+    }
+
     /// Performs SSL read, returning SSL error
     ///
     /// Also implements [`std::io::Read`]
@@ -128,11 +669,13 @@ impl Ssl {
         // Can't read with empty buffer because ret == 0 means error
         if buf.is_empty() { return Ok(0); }
         let len = usize::min(buf.len(), c_int::MAX as usize) as c_int;
+        ErrorStack::clear();
         let ret = unsafe { sys::SSL_read(self.0, buf.as_mut_ptr(), len) };
+        self.resume_panic();
         if ret <= 0 {
             let err = self.make_error(ret);
             if matches!(err, SslError::ZeroReturn) { return Ok(0); }
-            return Err(err.into());
+            return Err(err);
         }
         Ok(ret as usize)
     }
@@ -145,9 +688,11 @@ impl Ssl {
         // Can't write with empty buffer because ret == 0 means error
         if buf.is_empty() { return Ok(0); }
         let len = usize::min(buf.len(), c_int::MAX as usize) as c_int;
+        ErrorStack::clear();
         let ret = unsafe { sys::SSL_write(self.0, buf.as_ptr(), len) };
+        self.resume_panic();
         if ret <= 0 {
-            return Err(self.make_error(ret).into());
+            return Err(self.make_error(ret));
         }
         Ok(ret as usize)
     }
@@ -171,7 +716,10 @@ impl Write for Ssl {
 
 impl Drop for Ssl {
     fn drop(&mut self) {
-        let _ = self.shutdown();
+        if self.can_send_close_notify() {
+            // Don't panic while dropping, a panicking callback has already failed the connection
+            let _ = panic::catch_unwind(AssertUnwindSafe(|| self.close_notify_step()));
+        }
         unsafe { sys::SSL_free(self.0) };
     }
 }
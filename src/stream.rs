@@ -0,0 +1,376 @@
+use core::any::TypeId;
+use core::ffi::{c_char, c_int, c_long, c_void};
+use core::marker::PhantomData;
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::io::{self, Read, Write, ErrorKind};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Mutex;
+
+use crate::sys;
+use crate::{Ssl, ErrorStack, SslError};
+
+/// [`Ssl`] that owns its stream, implements [`std::io::Read`] and [`std::io::Write`]
+///
+/// Works over any [`Read`] + [`Write`] type, not only sockets:
+/// ```
+/// # fn main() -> std::io::Result<()> {
+/// # use openssl_lite::{SslCtx, Ssl, SslStream};
+/// let ctx = SslCtx::new()?;
+/// let mut ssl = Ssl::new(&ctx)?;
+/// ssl.set_hostname(c"Neltharion01.github.io")?;
+/// # /*
+/// let socket = TcpStream::connect("Neltharion01.github.io:443")?;
+/// let mut stream = SslStream::new(ssl, socket)?;
+/// stream.connect()?;
+///
+/// // Now call stream.read(), stream.write()
+///
+/// // After you are done, close the connection
+/// stream.send_close_notify()?;
+/// # */
+/// # Ok(())
+/// # }
+/// ```
+/// Non-blocking streams are supported: operations fail with [`SslError::WantRead`] or [`SslError::WantWrite`]
+/// (`WouldBlock` for `Read` and `Write`) and have to be retried
+pub struct SslStream<S> {
+    // Owns the BIO, which owns the stream
+    ssl: Ssl,
+    _marker: PhantomData<S>,
+}
+
+/// Data of the BIO
+struct StreamState<S> {
+    stream: S,
+    // Last error of the stream, OpenSSL only sees -1
+    error: Option<io::Error>,
+    // Panic from the stream methods, resumed after the SSL call
+    panic: Option<Box<dyn Any + Send>>,
+}
+
+impl<S: Read + Write + 'static> SslStream<S> {
+    /// Wraps the stream, all TLS traffic of `ssl` goes through it
+    #[doc(alias = "SSL_set_bio")]
+    pub fn new(ssl: Ssl, stream: S) -> Result<SslStream<S>, ErrorStack> {
+        ErrorStack::clear();
+        let bio = unsafe { sys::BIO_new(bio_method::<S>()?) };
+        if bio.is_null() { return Err(ErrorStack::get()); }
+
+        let state = Box::new(StreamState { stream, error: None, panic: None });
+        unsafe {
+            sys::BIO_set_data(bio, Box::into_raw(state) as *mut c_void);
+            sys::BIO_set_init(bio, 1);
+            // Takes ownership of the BIO, once for reading and once for writing
+            sys::SSL_set_bio(ssl.as_ptr(), bio, bio);
+        }
+        Ok(SslStream { ssl, _marker: PhantomData })
+    }
+
+    /// Performs the SSL connection as a client
+    pub fn connect(&mut self) -> Result<(), SslError> {
+        let ret = self.ssl.connect();
+        self.check(ret)
+    }
+
+    /// Accepts the SSL connection as a server
+    pub fn accept(&mut self) -> Result<(), SslError> {
+        let ret = self.ssl.accept();
+        self.check(ret)
+    }
+
+    /// Starts the connection as a client, for non-blocking streams
+    ///
+    /// Unlike [`SslStream::connect`], returns a typed state to resume the handshake from:
+    /// ```
+    /// # use openssl_lite::{SslStream, SslError, HandshakeResult};
+    /// # fn wait(readable: bool) {}
+    /// # fn run<S: std::io::Read + std::io::Write + 'static>(stream: SslStream<S>) -> Result<SslStream<S>, SslError> {
+    /// let mut result = stream.start_connect()?;
+    /// let stream = loop {
+    ///     match result {
+    ///         HandshakeResult::Done(stream) => break stream,
+    ///         HandshakeResult::WouldBlock(mid) => {
+    ///             // Wait for the socket with poll, mio, etc.
+    ///             wait(mid.wants_read());
+    ///             result = mid.handshake()?;
+    ///         }
+    ///     }
+    /// };
+    /// # Ok(stream)
+    /// # }
+    /// ```
+    pub fn start_connect(mut self) -> Result<HandshakeResult<S>, SslError> {
+        let ret = self.ssl.connect();
+        HandshakeResult::new(self, ret)
+    }
+
+    /// Starts accepting the connection as a server, for non-blocking streams
+    ///
+    /// Check [`SslStream::start_connect`] for details
+    pub fn start_accept(mut self) -> Result<HandshakeResult<S>, SslError> {
+        let ret = self.ssl.accept();
+        HandshakeResult::new(self, ret)
+    }
+
+    /// Sends `close_notify` without waiting for the peer's one, same as [`SslStream::send_close_notify`]
+    ///
+    /// The stream stays open, so the peer's remaining data and `close_notify` can still be read.
+    /// Check [`SslStream::shutdown_bidirectional`] to wait for the peer instead
+    pub fn shutdown(&mut self) -> Result<(), SslError> {
+        self.send_close_notify()
+    }
+
+    /// Sends `close_notify`, check [`Ssl::send_close_notify`]
+    pub fn send_close_notify(&mut self) -> Result<(), SslError> {
+        let ret = self.ssl.send_close_notify();
+        self.check(ret)
+    }
+
+    /// Sends `close_notify` and waits for the peer's one, check [`Ssl::shutdown_bidirectional`]
+    pub fn shutdown_bidirectional(&mut self) -> Result<(), SslError> {
+        let ret = self.ssl.shutdown_bidirectional();
+        self.check(ret)
+    }
+
+    /// Performs SSL read, returning SSL error
+    pub fn ssl_read(&mut self, buf: &mut [u8]) -> Result<usize, SslError> {
+        let ret = self.ssl.ssl_read(buf);
+        self.check(ret)
+    }
+
+    /// Performs SSL write, returning SSL error
+    pub fn ssl_write(&mut self, buf: &[u8]) -> Result<usize, SslError> {
+        let ret = self.ssl.ssl_write(buf);
+        self.check(ret)
+    }
+
+    /// Returns the SSL object, e.g. to inspect the peer certificate
+    pub fn ssl(&self) -> &Ssl {
+        &self.ssl
+    }
+
+    /// Returns a reference to the underlying stream
+    pub fn get_ref(&self) -> &S {
+        unsafe { &(*self.state()).stream }
+    }
+
+    /// Returns a mutable reference to the underlying stream
+    ///
+    /// Reading or writing it directly will corrupt the TLS connection
+    pub fn get_mut(&mut self) -> &mut S {
+        unsafe { &mut (*self.state()).stream }
+    }
+
+    /// Returns the underlying stream without closing the connection
+    pub fn into_inner(self) -> S {
+        let bio = unsafe { sys::SSL_get_rbio(self.ssl.as_ptr()) };
+        let state = unsafe { Box::from_raw(sys::BIO_get_data(bio) as *mut StreamState<S>) };
+        // The BIO now fails all I/O, including close_notify sent on drop
+        unsafe { sys::BIO_set_data(bio, core::ptr::null_mut()) };
+        drop(self);
+        state.stream
+    }
+
+    fn state(&self) -> *mut StreamState<S> {
+        unsafe { sys::BIO_get_data(sys::SSL_get_rbio(self.ssl.as_ptr())) as *mut StreamState<S> }
+    }
+
+    /// Replaces the generic syscall error with the actual stream error
+    fn check<T>(&mut self, ret: Result<T, SslError>) -> Result<T, SslError> {
+        let state = unsafe { &mut *self.state() };
+        if let Some(payload) = state.panic.take() {
+            panic::resume_unwind(payload);
+        }
+        match (ret, state.error.take()) {
+            // The stream failed, whatever OpenSSL made of it
+            (Err(SslError::Syscall(_) | SslError::UnexpectedEof), Some(err)) => Err(SslError::Syscall(err)),
+            // errno is meaningless here, the stream just ended
+            (Err(SslError::Syscall(_)), None) => Err(SslError::UnexpectedEof),
+            (ret, _) => ret,
+        }
+    }
+}
+
+impl<S: Read + Write + 'static> Read for SslStream<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.ssl_read(buf).map_err(|e| e.into())
+    }
+}
+
+impl<S: Read + Write + 'static> Write for SslStream<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.ssl_write(buf).map_err(|e| e.into())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.get_mut().flush()
+    }
+}
+
+impl<S: core::fmt::Debug> core::fmt::Debug for SslStream<S> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let state = unsafe { &*(sys::BIO_get_data(sys::SSL_get_rbio(self.ssl.as_ptr())) as *const StreamState<S>) };
+        f.debug_struct("SslStream").field("ssl", &self.ssl).field("stream", &state.stream).finish()
+    }
+}
+
+/// Progress of a non-blocking handshake
+#[derive(Debug)]
+pub enum HandshakeResult<S> {
+    /// The handshake has completed
+    Done(SslStream<S>),
+    /// The stream is not ready, the handshake has to be resumed later
+    WouldBlock(MidHandshake<S>),
+}
+
+impl<S: Read + Write + 'static> HandshakeResult<S> {
+    fn new(mut stream: SslStream<S>, ret: Result<(), SslError>) -> Result<HandshakeResult<S>, SslError> {
+        match stream.check(ret) {
+            Ok(()) => Ok(HandshakeResult::Done(stream)),
+            Err(SslError::WantRead) => Ok(HandshakeResult::WouldBlock(MidHandshake { stream, wants_read: true })),
+            Err(SslError::WantWrite) => Ok(HandshakeResult::WouldBlock(MidHandshake { stream, wants_read: false })),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+/// Handshake waiting for the stream to become readable or writable
+#[derive(Debug)]
+pub struct MidHandshake<S> {
+    stream: SslStream<S>,
+    wants_read: bool,
+}
+
+impl<S: Read + Write + 'static> MidHandshake<S> {
+    /// Resumes the handshake, after the stream became ready
+    #[doc(alias = "SSL_do_handshake")]
+    pub fn handshake(mut self) -> Result<HandshakeResult<S>, SslError> {
+        let ret = self.stream.ssl.do_handshake();
+        HandshakeResult::new(self.stream, ret)
+    }
+
+    /// Returns true if the handshake waits for the stream to become readable, false if writable
+    pub fn wants_read(&self) -> bool {
+        self.wants_read
+    }
+
+    /// Returns the SSL object
+    pub fn ssl(&self) -> &Ssl {
+        &self.stream.ssl
+    }
+
+    /// Returns a reference to the underlying stream, e.g. to register it in the event loop
+    pub fn get_ref(&self) -> &S {
+        self.stream.get_ref()
+    }
+
+    /// Returns a mutable reference to the underlying stream
+    pub fn get_mut(&mut self) -> &mut S {
+        self.stream.get_mut()
+    }
+}
+
+/// Returns the BIO method calling into streams of type `S`, created once per type
+fn bio_method<S: Read + Write + 'static>() -> Result<*const sys::BIO_METHOD, ErrorStack> {
+    // Pointers are stored as usize to be Send
+    static METHODS: Mutex<BTreeMap<TypeId, usize>> = Mutex::new(BTreeMap::new());
+
+    let mut methods = METHODS.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(&ptr) = methods.get(&TypeId::of::<S>()) {
+        return Ok(ptr as *const sys::BIO_METHOD);
+    }
+
+    let kind = unsafe { sys::BIO_get_new_index() };
+    if kind == -1 { return Err(ErrorStack::get()); }
+    let ptr = unsafe { sys::BIO_meth_new(kind | sys::BIO_TYPE_SOURCE_SINK, c"rust stream".as_ptr()) };
+    if ptr.is_null() { return Err(ErrorStack::get()); }
+
+    unsafe {
+        sys::BIO_meth_set_write(ptr, Some(bio_write::<S>));
+        sys::BIO_meth_set_read(ptr, Some(bio_read::<S>));
+        sys::BIO_meth_set_ctrl(ptr, Some(bio_ctrl::<S>));
+        sys::BIO_meth_set_destroy(ptr, Some(bio_destroy::<S>));
+    }
+    // Never freed, BIOs may outlive any particular stream
+    methods.insert(TypeId::of::<S>(), ptr as usize);
+    Ok(ptr)
+}
+
+/// Runs `f` on the stream of the BIO, storing errors and panics for [`SslStream::check`]
+///
+/// On error, returns true if the operation should be retried later
+unsafe fn with_stream<S, T>(bio: *mut sys::BIO, f: impl FnOnce(&mut S) -> io::Result<T>) -> Result<T, bool> {
+    let state = unsafe { sys::BIO_get_data(bio) as *mut StreamState<S> };
+    // Detached by `into_inner`
+    let Some(state) = (unsafe { state.as_mut() }) else { return Err(false) };
+
+    match panic::catch_unwind(AssertUnwindSafe(|| f(&mut state.stream))) {
+        Ok(Ok(v)) => Ok(v),
+        Ok(Err(err)) => {
+            let retry = err.kind() == ErrorKind::WouldBlock;
+            state.error = Some(err);
+            Err(retry)
+        }
+        Err(payload) => {
+            state.panic = Some(payload);
+            Err(false)
+        }
+    }
+}
+
+unsafe extern "C" fn bio_read<S: Read>(bio: *mut sys::BIO, buf: *mut c_char, len: c_int) -> c_int {
+    unsafe { sys::BIO_clear_retry_flags(bio) };
+    let buf = unsafe { core::slice::from_raw_parts_mut(buf as *mut u8, len as usize) };
+    let ret = unsafe { with_stream(bio, |stream: &mut S| loop {
+        match stream.read(buf) {
+            Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+            other => break other,
+        }
+    }) };
+    match ret {
+        Ok(n) => n as c_int,
+        Err(retry) => {
+            if retry { unsafe { sys::BIO_set_retry_read(bio) }; }
+            -1
+        }
+    }
+}
+
+unsafe extern "C" fn bio_write<S: Write>(bio: *mut sys::BIO, buf: *const c_char, len: c_int) -> c_int {
+    unsafe { sys::BIO_clear_retry_flags(bio) };
+    let buf = unsafe { core::slice::from_raw_parts(buf as *const u8, len as usize) };
+    let ret = unsafe { with_stream(bio, |stream: &mut S| loop {
+        match stream.write(buf) {
+            Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+            other => break other,
+        }
+    }) };
+    match ret {
+        Ok(n) => n as c_int,
+        Err(retry) => {
+            if retry { unsafe { sys::BIO_set_retry_write(bio) }; }
+            -1
+        }
+    }
+}
+
+unsafe extern "C" fn bio_ctrl<S: Write>(bio: *mut sys::BIO, cmd: c_int, _larg: c_long, _parg: *mut c_void) -> c_long {
+    if cmd != sys::BIO_CTRL_FLUSH { return 0; }
+    match unsafe { with_stream(bio, |stream: &mut S| stream.flush()) } {
+        Ok(()) => 1,
+        Err(_) => 0,
+    }
+}
+unsafe extern "C" fn bio_destroy<S>(bio: *mut sys::BIO) -> c_int {
+    if bio.is_null() { return 0; }
+    let state = unsafe { sys::BIO_get_data(bio) as *mut StreamState<S> };
+    if !state.is_null() {
+        drop(unsafe { Box::from_raw(state) });
+    }
+    unsafe {
+        sys::BIO_set_data(bio, core::ptr::null_mut());
+        sys::BIO_set_init(bio, 0);
+    }
+    1
+}
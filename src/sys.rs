@@ -1,6 +1,6 @@
-#![allow(non_camel_case_types, non_snake_case, non_upper_case_globals)]
+#![allow(non_camel_case_types, non_snake_case, non_upper_case_globals, clippy::upper_case_acronyms)]
 
-use core::ffi::{c_int, c_ulong, c_long, c_char, c_void};
+use core::ffi::{c_int, c_uint, c_ulong, c_long, c_char, c_void};
 
 // Those are uninhabited void pointers
 #[repr(C)]
@@ -9,16 +9,192 @@ pub struct SSL([u8; 0]);
 pub struct SSL_CTX([u8; 0]);
 #[repr(C)]
 pub struct SSL_METHOD([u8; 0]);
+#[repr(C)]
+pub struct CRYPTO_EX_DATA([u8; 0]);
+#[repr(C)]
+pub struct BIO([u8; 0]);
+#[repr(C)]
+pub struct X509([u8; 0]);
+#[repr(C)]
+pub struct EVP_PKEY([u8; 0]);
+#[repr(C)]
+pub struct X509_STORE([u8; 0]);
+#[repr(C)]
+pub struct X509_NAME([u8; 0]);
+#[repr(C)]
+pub struct X509_STORE_CTX([u8; 0]);
+#[repr(C)]
+pub struct X509_NAME_ENTRY([u8; 0]);
+#[repr(C)]
+pub struct ASN1_STRING([u8; 0]);
+#[repr(C)]
+pub struct ASN1_OBJECT([u8; 0]);
+#[repr(C)]
+pub struct BIGNUM([u8; 0]);
+#[repr(C)]
+pub struct BIO_METHOD([u8; 0]);
+#[repr(C)]
+pub struct EVP_MD([u8; 0]);
+#[repr(C)]
+pub struct GENERAL_NAME([u8; 0]);
+#[repr(C)]
+pub struct SSL_CIPHER([u8; 0]);
+#[repr(C)]
+pub struct SSL_SESSION([u8; 0]);
+#[repr(C)]
+pub struct EVP_CIPHER([u8; 0]);
+#[repr(C)]
+pub struct EVP_CIPHER_CTX([u8; 0]);
+#[repr(C)]
+pub struct EVP_MAC_CTX([u8; 0]);
+#[repr(C)]
+pub struct ENGINE([u8; 0]);
+#[repr(C)]
+pub struct X509_VERIFY_PARAM([u8; 0]);
+
+#[repr(C)]
+pub struct OSSL_PARAM {
+    pub key: *const c_char,
+    pub data_type: c_uint,
+    pub data: *mut c_void,
+    pub data_size: usize,
+    pub return_size: usize,
+}
+
+pub type ASN1_INTEGER = ASN1_STRING;
+pub type ASN1_TIME = ASN1_STRING;
+
+/// `struct tm`, with extra space for platform-specific trailing fields
+#[repr(C)]
+#[derive(Default)]
+pub struct tm {
+    pub tm_sec: c_int,
+    pub tm_min: c_int,
+    pub tm_hour: c_int,
+    pub tm_mday: c_int,
+    pub tm_mon: c_int,
+    pub tm_year: c_int,
+    pub tm_wday: c_int,
+    pub tm_yday: c_int,
+    pub tm_isdst: c_int,
+    pub _reserved: [usize; 4],
+}
+#[repr(C)]
+pub struct OPENSSL_STACK([u8; 0]);
+
+pub type CRYPTO_EX_free = unsafe extern "C" fn(parent: *mut c_void, ptr: *mut c_void, ad: *mut CRYPTO_EX_DATA, idx: c_int, argl: c_long, argp: *mut c_void);
+pub type CRYPTO_EX_dup = unsafe extern "C" fn(to: *mut CRYPTO_EX_DATA, from: *const CRYPTO_EX_DATA, from_d: *mut c_void, idx: c_int, argl: c_long, argp: *mut c_void) -> c_int;
+pub type CRYPTO_EX_new = unsafe extern "C" fn(parent: *mut c_void, ptr: *mut c_void, ad: *mut CRYPTO_EX_DATA, idx: c_int, argl: c_long, argp: *mut c_void);
+
+pub const CRYPTO_EX_INDEX_SSL: c_int = 0;
+pub const CRYPTO_EX_INDEX_SSL_CTX: c_int = 1;
+
+pub type SSL_CTX_new_session_cb = unsafe extern "C" fn(ssl: *mut SSL, sess: *mut SSL_SESSION) -> c_int;
+pub type SSL_CTX_ticket_key_evp_cb = unsafe extern "C" fn(ssl: *mut SSL, key_name: *mut u8, iv: *mut u8, ctx: *mut EVP_CIPHER_CTX, hctx: *mut EVP_MAC_CTX, enc: c_int) -> c_int;
+pub type SSL_info_cb = unsafe extern "C" fn(ssl: *const SSL, type_: c_int, val: c_int);
+pub type SSL_msg_cb = unsafe extern "C" fn(write_p: c_int, version: c_int, content_type: c_int, buf: *const c_void, len: usize, ssl: *mut SSL, arg: *mut c_void);
+pub type SSL_CTX_keylog_cb_func = unsafe extern "C" fn(ssl: *const SSL, line: *const c_char);
+pub type SSL_servername_cb = unsafe extern "C" fn(ssl: *mut SSL, al: *mut c_int, arg: *mut c_void) -> c_int;
+pub type SSL_verify_cb = unsafe extern "C" fn(preverify_ok: c_int, x509_ctx: *mut X509_STORE_CTX) -> c_int;
+
+pub type SSL_CTX_alpn_select_cb_func = unsafe extern "C" fn(ssl: *mut SSL, out: *mut *const u8, outlen: *mut u8, _in: *const u8, inlen: u32, arg: *mut c_void) -> c_int;
 
 pub const SSL_VERIFY_NONE: c_int = 0;
 pub const SSL_VERIFY_PEER: c_int = 1;
+pub const SSL_VERIFY_FAIL_IF_NO_PEER_CERT: c_int = 2;
+pub const SSL_VERIFY_CLIENT_ONCE: c_int = 4;
+pub const SSL_VERIFY_POST_HANDSHAKE: c_int = 8;
+
+pub const SSL_SENT_SHUTDOWN: c_int = 1;
+pub const SSL_RECEIVED_SHUTDOWN: c_int = 2;
 
 pub const SSL_CTRL_SET_MIN_PROTO_VERSION: c_int = 123;
+pub const SSL_CTRL_SET_MAX_PROTO_VERSION: c_int = 124;
+pub const SSL_CTRL_GET_MIN_PROTO_VERSION: c_int = 130;
+pub const SSL_CTRL_GET_MAX_PROTO_VERSION: c_int = 131;
+pub const SSL_CTRL_SET_TLSEXT_SERVERNAME_CB: c_int = 53;
 pub const SSL_CTRL_SET_TLSEXT_HOSTNAME: c_int = 55;
+pub const SSL_CTRL_SET_SESS_CACHE_SIZE: c_int = 42;
+pub const SSL_CTRL_SET_SESS_CACHE_MODE: c_int = 44;
+pub const SSL_CTRL_GET_SESS_CACHE_MODE: c_int = 45;
+pub const SSL_CTRL_CHAIN: c_int = 88;
+pub const SSL_CTRL_CHAIN_CERT: c_int = 89;
+pub const SSL_CTRL_GET_PEER_SIGNATURE_NID: c_int = 108;
+pub const SSL_CTRL_SET_GROUPS_LIST: c_int = 92;
+pub const SSL_CTRL_SET_SIGALGS_LIST: c_int = 98;
+pub const SSL_CTRL_SET_CLIENT_SIGALGS_LIST: c_int = 102;
+pub const SSL_CTRL_GET_NEGOTIATED_GROUP: c_int = 134;
 
 pub const TLSEXT_NAMETYPE_host_name: c_long = 0;
 
 pub const SSL_FILETYPE_PEM: c_int = 1;
+pub const SSL_FILETYPE_ASN1: c_int = 2;
+
+pub const NID_undef: c_int = 0;
+pub const NID_rsaEncryption: c_int = 6;
+pub const NID_dsa: c_int = 116;
+pub const NID_X9_62_id_ecPublicKey: c_int = 408;
+pub const NID_rsassaPss: c_int = 912;
+pub const NID_ED25519: c_int = 1087;
+pub const NID_ED448: c_int = 1088;
+pub const NID_commonName: c_int = 13;
+pub const NID_subject_alt_name: c_int = 85;
+
+pub const GEN_EMAIL: c_int = 1;
+pub const GEN_DNS: c_int = 2;
+pub const GEN_URI: c_int = 6;
+pub const GEN_IPADD: c_int = 7;
+
+pub const BIO_CTRL_INFO: c_int = 3;
+pub const BIO_CTRL_FLUSH: c_int = 11;
+pub const BIO_TYPE_SOURCE_SINK: c_int = 0x0400;
+pub const BIO_FLAGS_READ: c_int = 0x01;
+pub const BIO_FLAGS_WRITE: c_int = 0x02;
+pub const BIO_FLAGS_RWS: c_int = 0x07;
+pub const BIO_FLAGS_SHOULD_RETRY: c_int = 0x08;
+
+pub const ASN1_STRFLGS_ESC_MSB: c_ulong = 4;
+pub const ASN1_STRFLGS_RFC2253: c_ulong = 0x317;
+pub const XN_FLAG_SEP_CPLUS_SPC: c_ulong = 2 << 16;
+
+pub const EVP_MAX_MD_SIZE: usize = 64;
+
+pub const ERR_TXT_STRING: c_int = 0x02;
+pub const ERR_LIB_SYS: c_int = 2;
+pub const ERR_LIB_PEM: c_int = 9;
+pub const ERR_LIB_SSL: c_int = 20;
+pub const PEM_R_NO_START_LINE: c_int = 108;
+
+pub const SSL_SESS_CACHE_OFF: c_long = 0x0000;
+pub const SSL_SESS_CACHE_CLIENT: c_long = 0x0001;
+pub const SSL_SESS_CACHE_SERVER: c_long = 0x0002;
+pub const SSL_SESS_CACHE_NO_AUTO_CLEAR: c_long = 0x0080;
+pub const SSL_SESS_CACHE_NO_INTERNAL_LOOKUP: c_long = 0x0100;
+pub const SSL_SESS_CACHE_NO_INTERNAL_STORE: c_long = 0x0200;
+
+pub const SSL_MAX_SID_CTX_LENGTH: usize = 32;
+pub const EVP_MAX_IV_LENGTH: usize = 16;
+
+pub const SSL_TLSEXT_ERR_OK: c_int = 0;
+pub const SSL_TLSEXT_ERR_ALERT_FATAL: c_int = 2;
+pub const SSL_TLSEXT_ERR_NOACK: c_int = 3;
+pub const SSL_AD_INTERNAL_ERROR: c_int = 80;
+pub const SSL_AD_UNRECOGNIZED_NAME: c_int = 112;
+pub const SSL_AD_REASON_OFFSET: c_int = 1000;
+
+pub const SSL_CB_LOOP: c_int = 0x01;
+pub const SSL_CB_EXIT: c_int = 0x02;
+pub const SSL_CB_READ: c_int = 0x04;
+pub const SSL_CB_ALERT: c_int = 0x4000;
+pub const SSL_CB_HANDSHAKE_START: c_int = 0x10;
+pub const SSL_CB_HANDSHAKE_DONE: c_int = 0x20;
+
+pub const SSL3_RT_CHANGE_CIPHER_SPEC: c_int = 20;
+pub const SSL3_RT_ALERT: c_int = 21;
+pub const SSL3_RT_HANDSHAKE: c_int = 22;
+pub const SSL3_RT_APPLICATION_DATA: c_int = 23;
+pub const SSL3_RT_HEADER: c_int = 0x100;
+pub const SSL3_RT_INNER_CONTENT_TYPE: c_int = 0x101;
 
 pub mod error {
     use core::ffi::c_int;
@@ -33,31 +209,237 @@ pub mod error {
 unsafe extern "C" {
     pub fn TLS_method() -> *const SSL_METHOD;
     pub fn SSL_CTX_new(method: *const SSL_METHOD) -> *mut SSL_CTX;
-    //pub fn SSL_CTX_set_alpn_protos(ctx: *mut SSL_CTX, protos: *const u8, protos_len: c_int) -> c_int;
+    pub fn SSL_CTX_set_alpn_protos(ctx: *mut SSL_CTX, protos: *const u8, protos_len: u32) -> c_int;
+    pub fn SSL_CTX_set_alpn_select_cb(ctx: *mut SSL_CTX, cb: Option<SSL_CTX_alpn_select_cb_func>, arg: *mut c_void);
+    pub fn SSL_CTX_set_keylog_callback(ctx: *mut SSL_CTX, cb: Option<SSL_CTX_keylog_cb_func>);
+    pub fn SSL_CTX_set_info_callback(ctx: *mut SSL_CTX, cb: Option<SSL_info_cb>);
+    pub fn SSL_CTX_set_msg_callback(ctx: *mut SSL_CTX, cb: Option<SSL_msg_cb>);
+    pub fn SSL_state_string_long(ssl: *const SSL) -> *const c_char;
+    pub fn SSL_is_server(ssl: *const SSL) -> c_int;
+    pub fn SSL_alert_desc_string_long(value: c_int) -> *const c_char;
     pub fn SSL_CTX_set_default_verify_paths(ctx: *mut SSL_CTX) -> c_int;
-    pub fn SSL_CTX_set_verify(ctx: *mut SSL_CTX, mode: c_int, verify_callback: *const c_void);
+    pub fn SSL_CTX_load_verify_file(ctx: *mut SSL_CTX, file: *const c_char) -> c_int;
+    pub fn SSL_CTX_load_verify_dir(ctx: *mut SSL_CTX, path: *const c_char) -> c_int;
+    pub fn SSL_CTX_get_cert_store(ctx: *const SSL_CTX) -> *mut X509_STORE;
+    pub fn SSL_CTX_set_cert_store(ctx: *mut SSL_CTX, store: *mut X509_STORE);
+    pub fn SSL_CTX_set_verify(ctx: *mut SSL_CTX, mode: c_int, verify_callback: Option<SSL_verify_cb>);
+    pub fn SSL_CTX_get_verify_callback(ctx: *const SSL_CTX) -> Option<SSL_verify_cb>;
+    pub fn SSL_CTX_get_verify_mode(ctx: *const SSL_CTX) -> c_int;
+    pub fn SSL_CTX_set_client_CA_list(ctx: *mut SSL_CTX, list: *mut OPENSSL_STACK);
     pub fn SSL_CTX_ctrl(ctx: *mut SSL_CTX, cmd: c_int, larg: c_long, parg: *mut c_void) -> c_long;
+    pub fn SSL_CTX_callback_ctrl(ctx: *mut SSL_CTX, cmd: c_int, fp: Option<unsafe extern "C" fn()>) -> c_long;
+    pub fn SSL_CTX_use_certificate(ctx: *mut SSL_CTX, x: *mut X509) -> c_int;
+    pub fn SSL_CTX_use_PrivateKey(ctx: *mut SSL_CTX, pkey: *mut EVP_PKEY) -> c_int;
+    pub fn SSL_CTX_use_certificate_chain_file(ctx: *mut SSL_CTX, file: *const c_char) -> c_int;
     pub fn SSL_CTX_use_certificate_file(ctx: *mut SSL_CTX, file: *const c_char, _type: c_int) -> c_int;
     pub fn SSL_CTX_use_PrivateKey_file(ctx: *mut SSL_CTX, file: *const c_char, _type: c_int) -> c_int;
     pub fn SSL_CTX_check_private_key(ctx: *mut SSL_CTX) -> c_int;
     pub fn SSL_CTX_set_cipher_list(ctx: *mut SSL_CTX, s: *const c_char) -> c_int;
+    pub fn SSL_CTX_set_ciphersuites(ctx: *mut SSL_CTX, s: *const c_char) -> c_int;
     pub fn SSL_CTX_set_options(ctx: *mut SSL_CTX, options: u64) -> u64;
+    pub fn SSL_CTX_set_timeout(ctx: *mut SSL_CTX, t: c_long) -> c_long;
+    pub fn SSL_CTX_set_num_tickets(ctx: *mut SSL_CTX, num_tickets: usize) -> c_int;
+    pub fn SSL_CTX_set_session_id_context(ctx: *mut SSL_CTX, sid_ctx: *const u8, sid_ctx_len: c_uint) -> c_int;
+    pub fn SSL_CTX_set_tlsext_ticket_key_evp_cb(ctx: *mut SSL_CTX, fp: Option<SSL_CTX_ticket_key_evp_cb>) -> c_int;
+    pub fn SSL_CTX_sess_set_new_cb(ctx: *mut SSL_CTX, cb: Option<SSL_CTX_new_session_cb>);
+    pub fn SSL_CTX_get_ex_data(ctx: *const SSL_CTX, idx: c_int) -> *mut c_void;
+    pub fn SSL_CTX_set_ex_data(ctx: *mut SSL_CTX, idx: c_int, data: *mut c_void) -> c_int;
     pub fn SSL_CTX_free(ctx: *mut SSL_CTX);
 
     pub fn SSL_new(ctx: *mut SSL_CTX) -> *mut SSL;
     pub fn SSL_ctrl(ctx: *mut SSL, cmd: c_int, larg: c_long, parg: *mut c_void) -> c_long;
     pub fn SSL_set1_host(ssl: *mut SSL, name: *const c_char) -> c_int;
+    pub fn SSL_set_ciphersuites(ssl: *mut SSL, s: *const c_char) -> c_int;
+    pub fn SSL_get0_param(ssl: *mut SSL) -> *mut X509_VERIFY_PARAM;
+    pub fn SSL_set_verify(ssl: *mut SSL, mode: c_int, verify_callback: Option<SSL_verify_cb>);
+    pub fn SSL_get_verify_callback(ssl: *const SSL) -> Option<SSL_verify_cb>;
+    pub fn SSL_get_ex_data(ssl: *const SSL, idx: c_int) -> *mut c_void;
+    pub fn SSL_set_ex_data(ssl: *mut SSL, idx: c_int, data: *mut c_void) -> c_int;
+    pub fn SSL_get_ex_data_X509_STORE_CTX_idx() -> c_int;
+    pub fn SSL_get_verify_mode(ssl: *const SSL) -> c_int;
+    pub fn SSL_use_certificate(ssl: *mut SSL, x: *mut X509) -> c_int;
+    pub fn SSL_use_PrivateKey(ssl: *mut SSL, pkey: *mut EVP_PKEY) -> c_int;
+    pub fn SSL_check_private_key(ssl: *const SSL) -> c_int;
+    pub fn SSL_load_client_CA_file(file: *const c_char) -> *mut OPENSSL_STACK;
     pub fn SSL_set_fd(ssl: *mut SSL, fd: c_int) -> c_int;
+    pub fn SSL_set_bio(ssl: *mut SSL, rbio: *mut BIO, wbio: *mut BIO);
+    pub fn SSL_get_rbio(ssl: *const SSL) -> *mut BIO;
     pub fn SSL_connect(ssl: *mut SSL) -> c_int;
     pub fn SSL_accept(ssl: *mut SSL) -> c_int;
+    pub fn SSL_do_handshake(ssl: *mut SSL) -> c_int;
+    pub fn SSL_is_init_finished(ssl: *const SSL) -> c_int;
     pub fn SSL_read(ssl: *mut SSL, buf: *mut u8, num: c_int) -> c_int;
+    pub fn SSL_peek(ssl: *mut SSL, buf: *mut u8, num: c_int) -> c_int;
     pub fn SSL_write(ssl: *mut SSL, buf: *const u8, num: c_int) -> c_int;
     pub fn SSL_get_error(ssl: *const SSL, ret: c_int) -> c_int;
     pub fn SSL_shutdown(ssl: *mut SSL) -> c_int;
+    pub fn SSL_get_shutdown(ssl: *const SSL) -> c_int;
+    pub fn SSL_version(ssl: *const SSL) -> c_int;
+    pub fn SSL_get_session(ssl: *const SSL) -> *mut SSL_SESSION;
+    pub fn SSL_get1_session(ssl: *mut SSL) -> *mut SSL_SESSION;
+    pub fn SSL_set_session(ssl: *mut SSL, session: *mut SSL_SESSION) -> c_int;
+    pub fn SSL_get_current_cipher(ssl: *const SSL) -> *const SSL_CIPHER;
+    pub fn SSL_group_to_name(ssl: *mut SSL, id: c_int) -> *const c_char;
+    pub fn SSL_get_peer_signature_type_nid(ssl: *const SSL, pnid: *mut c_int) -> c_int;
+    pub fn SSL_session_reused(ssl: *const SSL) -> c_int;
+    pub fn SSL_get1_peer_certificate(ssl: *const SSL) -> *mut X509;
+    pub fn SSL_get_peer_cert_chain(ssl: *const SSL) -> *mut OPENSSL_STACK;
+    pub fn SSL_get0_verified_chain(ssl: *const SSL) -> *mut OPENSSL_STACK;
+    pub fn SSL_get_client_CA_list(ssl: *const SSL) -> *mut OPENSSL_STACK;
+    pub fn SSL_get_verify_result(ssl: *const SSL) -> c_long;
+    pub fn SSL_get0_alpn_selected(ssl: *const SSL, data: *mut *const u8, len: *mut u32);
+    pub fn SSL_get_SSL_CTX(ssl: *const SSL) -> *mut SSL_CTX;
+    pub fn SSL_set_SSL_CTX(ssl: *mut SSL, ctx: *mut SSL_CTX) -> *mut SSL_CTX;
+    pub fn SSL_get_servername(ssl: *const SSL, _type: c_int) -> *const c_char;
     pub fn SSL_free(ssl: *mut SSL);
 
-    pub fn ERR_get_error() -> c_ulong;
-    pub fn ERR_error_string_n(e: c_ulong, buf: *mut c_char, len: usize);
+    pub fn CRYPTO_get_ex_new_index(class_index: c_int, argl: c_long, argp: *mut c_void, new_func: Option<CRYPTO_EX_new>, dup_func: Option<CRYPTO_EX_dup>, free_func: Option<CRYPTO_EX_free>) -> c_int;
+
+    pub fn BIO_new_mem_buf(buf: *const c_void, len: c_int) -> *mut BIO;
+    pub fn BIO_new(method: *const BIO_METHOD) -> *mut BIO;
+    pub fn BIO_s_mem() -> *const BIO_METHOD;
+    pub fn BIO_ctrl(bio: *mut BIO, cmd: c_int, larg: c_long, parg: *mut c_void) -> c_long;
+    pub fn BIO_free(bio: *mut BIO) -> c_int;
+    pub fn BIO_get_data(bio: *mut BIO) -> *mut c_void;
+    pub fn BIO_set_data(bio: *mut BIO, ptr: *mut c_void);
+    pub fn BIO_set_init(bio: *mut BIO, init: c_int);
+    pub fn BIO_set_flags(bio: *mut BIO, flags: c_int);
+    pub fn BIO_clear_flags(bio: *mut BIO, flags: c_int);
+    pub fn BIO_get_new_index() -> c_int;
+    pub fn BIO_meth_new(_type: c_int, name: *const c_char) -> *mut BIO_METHOD;
+    pub fn BIO_meth_set_write(biom: *mut BIO_METHOD, write: Option<unsafe extern "C" fn(*mut BIO, *const c_char, c_int) -> c_int>) -> c_int;
+    pub fn BIO_meth_set_read(biom: *mut BIO_METHOD, read: Option<unsafe extern "C" fn(*mut BIO, *mut c_char, c_int) -> c_int>) -> c_int;
+    pub fn BIO_meth_set_ctrl(biom: *mut BIO_METHOD, ctrl: Option<unsafe extern "C" fn(*mut BIO, c_int, c_long, *mut c_void) -> c_long>) -> c_int;
+    pub fn BIO_meth_set_destroy(biom: *mut BIO_METHOD, destroy: Option<unsafe extern "C" fn(*mut BIO) -> c_int>) -> c_int;
+
+    pub fn PEM_read_bio_X509(bp: *mut BIO, x: *mut *mut X509, cb: *const c_void, u: *mut c_void) -> *mut X509;
+    pub fn PEM_write_bio_X509(bp: *mut BIO, x: *const X509) -> c_int;
+    pub fn PEM_read_bio_PrivateKey(bp: *mut BIO, x: *mut *mut EVP_PKEY, cb: *const c_void, u: *mut c_void) -> *mut EVP_PKEY;
+
+    pub fn d2i_X509(a: *mut *mut X509, pp: *mut *const u8, length: c_long) -> *mut X509;
+    pub fn i2d_X509(x: *const X509, out: *mut *mut u8) -> c_int;
+    pub fn X509_VERIFY_PARAM_set1_ip(param: *mut X509_VERIFY_PARAM, ip: *const u8, iplen: usize) -> c_int;
+    pub fn X509_VERIFY_PARAM_set1_host(param: *mut X509_VERIFY_PARAM, name: *const c_char, namelen: usize) -> c_int;
+    pub fn X509_get_issuer_name(x: *const X509) -> *mut X509_NAME;
+    pub fn X509_get0_serialNumber(x: *const X509) -> *const ASN1_INTEGER;
+    pub fn X509_get0_notBefore(x: *const X509) -> *const ASN1_TIME;
+    pub fn X509_get0_notAfter(x: *const X509) -> *const ASN1_TIME;
+    pub fn X509_get_pubkey(x: *mut X509) -> *mut EVP_PKEY;
+    pub fn X509_get_signature_nid(x: *const X509) -> c_int;
+    pub fn X509_get_ext_d2i(x: *const X509, nid: c_int, crit: *mut c_int, idx: *mut c_int) -> *mut c_void;
+    pub fn X509_digest(x: *const X509, md: *const EVP_MD, out: *mut u8, len: *mut u32) -> c_int;
+    pub fn X509_up_ref(x: *mut X509) -> c_int;
+    pub fn X509_free(x: *mut X509);
+
+    pub fn X509_get_subject_name(x: *const X509) -> *mut X509_NAME;
+    pub fn X509_NAME_dup(name: *const X509_NAME) -> *mut X509_NAME;
+    pub fn X509_NAME_free(name: *mut X509_NAME);
+    pub fn X509_NAME_entry_count(name: *const X509_NAME) -> c_int;
+    pub fn X509_NAME_get_entry(name: *const X509_NAME, loc: c_int) -> *mut X509_NAME_ENTRY;
+    pub fn X509_NAME_get_index_by_NID(name: *const X509_NAME, nid: c_int, lastpos: c_int) -> c_int;
+    pub fn X509_NAME_print_ex(out: *mut BIO, name: *const X509_NAME, indent: c_int, flags: c_ulong) -> c_int;
+    pub fn X509_NAME_ENTRY_get_object(ne: *const X509_NAME_ENTRY) -> *mut ASN1_OBJECT;
+    pub fn X509_NAME_ENTRY_get_data(ne: *const X509_NAME_ENTRY) -> *mut ASN1_STRING;
+
+    pub fn GENERAL_NAME_get0_value(a: *const GENERAL_NAME, ptype: *mut c_int) -> *mut c_void;
+    pub fn GENERAL_NAMES_free(a: *mut OPENSSL_STACK);
+
+    pub fn ASN1_STRING_to_UTF8(out: *mut *mut u8, _in: *const ASN1_STRING) -> c_int;
+    pub fn ASN1_STRING_get0_data(x: *const ASN1_STRING) -> *const u8;
+    pub fn ASN1_STRING_length(x: *const ASN1_STRING) -> c_int;
+    pub fn ASN1_INTEGER_to_BN(ai: *const ASN1_INTEGER, bn: *mut BIGNUM) -> *mut BIGNUM;
+    pub fn ASN1_TIME_to_tm(s: *const ASN1_TIME, tm: *mut tm) -> c_int;
+
+    pub fn BN_num_bits(a: *const BIGNUM) -> c_int;
+    pub fn BN_bn2bin(a: *const BIGNUM, to: *mut u8) -> c_int;
+    pub fn BN_is_negative(b: *const BIGNUM) -> c_int;
+    pub fn BN_free(a: *mut BIGNUM);
+
+    pub fn OBJ_obj2nid(o: *const ASN1_OBJECT) -> c_int;
+    pub fn OBJ_obj2txt(buf: *mut c_char, buf_len: c_int, a: *const ASN1_OBJECT, no_name: c_int) -> c_int;
+    pub fn OBJ_nid2sn(n: c_int) -> *const c_char;
+
+    pub fn EVP_get_digestbyname(name: *const c_char) -> *const EVP_MD;
+    pub fn i2d_PUBKEY(a: *const EVP_PKEY, pp: *mut *mut u8) -> c_int;
+
+    pub fn EVP_aes_256_cbc() -> *const EVP_CIPHER;
+    pub fn EVP_EncryptInit_ex(ctx: *mut EVP_CIPHER_CTX, cipher: *const EVP_CIPHER, engine: *mut ENGINE, key: *const u8, iv: *const u8) -> c_int;
+    pub fn EVP_DecryptInit_ex(ctx: *mut EVP_CIPHER_CTX, cipher: *const EVP_CIPHER, engine: *mut ENGINE, key: *const u8, iv: *const u8) -> c_int;
+    pub fn EVP_MAC_CTX_set_params(ctx: *mut EVP_MAC_CTX, params: *const OSSL_PARAM) -> c_int;
+
+    pub fn OSSL_PARAM_construct_octet_string(key: *const c_char, buf: *mut c_void, bsize: usize) -> OSSL_PARAM;
+    pub fn OSSL_PARAM_construct_utf8_string(key: *const c_char, buf: *mut c_char, bsize: usize) -> OSSL_PARAM;
+    pub fn OSSL_PARAM_construct_end() -> OSSL_PARAM;
+
+    pub fn RAND_bytes(buf: *mut u8, num: c_int) -> c_int;
+
+    pub fn CRYPTO_free(ptr: *mut c_void, file: *const c_char, line: c_int);
+
+    pub fn OPENSSL_sk_new_null() -> *mut OPENSSL_STACK;
+    pub fn OPENSSL_sk_num(st: *const OPENSSL_STACK) -> c_int;
+    pub fn OPENSSL_sk_value(st: *const OPENSSL_STACK, i: c_int) -> *mut c_void;
+    pub fn OPENSSL_sk_push(st: *mut OPENSSL_STACK, data: *const c_void) -> c_int;
+    pub fn OPENSSL_sk_pop_free(st: *mut OPENSSL_STACK, func: Option<unsafe extern "C" fn(*mut c_void)>);
+
+    pub fn X509_STORE_CTX_get_ex_data(ctx: *const X509_STORE_CTX, idx: c_int) -> *mut c_void;
+    pub fn X509_STORE_CTX_get_current_cert(ctx: *const X509_STORE_CTX) -> *mut X509;
+    pub fn X509_STORE_CTX_get_error(ctx: *const X509_STORE_CTX) -> c_int;
+    pub fn X509_STORE_CTX_set_error(ctx: *mut X509_STORE_CTX, s: c_int);
+    pub fn X509_STORE_CTX_get_error_depth(ctx: *const X509_STORE_CTX) -> c_int;
+    pub fn X509_STORE_CTX_get0_chain(ctx: *const X509_STORE_CTX) -> *mut OPENSSL_STACK;
+
+    pub fn X509_verify_cert_error_string(n: c_long) -> *const c_char;
+
+    pub fn X509_STORE_new() -> *mut X509_STORE;
+    pub fn X509_STORE_add_cert(store: *mut X509_STORE, x: *mut X509) -> c_int;
+
+    pub fn d2i_AutoPrivateKey(a: *mut *mut EVP_PKEY, pp: *mut *const u8, length: c_long) -> *mut EVP_PKEY;
+    pub fn EVP_PKEY_up_ref(pkey: *mut EVP_PKEY) -> c_int;
+    pub fn EVP_PKEY_free(pkey: *mut EVP_PKEY);
+
+    pub fn SSL_SESSION_up_ref(s: *mut SSL_SESSION) -> c_int;
+    pub fn SSL_SESSION_free(s: *mut SSL_SESSION);
+    pub fn SSL_SESSION_is_resumable(s: *const SSL_SESSION) -> c_int;
+    pub fn SSL_SESSION_get_protocol_version(s: *const SSL_SESSION) -> c_int;
+    pub fn i2d_SSL_SESSION(s: *const SSL_SESSION, pp: *mut *mut u8) -> c_int;
+    pub fn d2i_SSL_SESSION(a: *mut *mut SSL_SESSION, pp: *mut *const u8, length: c_long) -> *mut SSL_SESSION;
+    pub fn PEM_read_bio_SSL_SESSION(bp: *mut BIO, x: *mut *mut SSL_SESSION, cb: *const c_void, u: *mut c_void) -> *mut SSL_SESSION;
+    pub fn PEM_write_bio_SSL_SESSION(bp: *mut BIO, x: *const SSL_SESSION) -> c_int;
+
+    pub fn SSL_CIPHER_get_name(c: *const SSL_CIPHER) -> *const c_char;
+    pub fn SSL_CIPHER_standard_name(c: *const SSL_CIPHER) -> *const c_char;
+    pub fn SSL_CIPHER_get_version(c: *const SSL_CIPHER) -> *const c_char;
+    pub fn SSL_CIPHER_get_protocol_id(c: *const SSL_CIPHER) -> u16;
+    pub fn SSL_CIPHER_get_bits(c: *const SSL_CIPHER, alg_bits: *mut c_int) -> c_int;
+
+    pub fn ERR_get_error_all(file: *mut *const c_char, line: *mut c_int, func: *mut *const c_char, data: *mut *const c_char, flags: *mut c_int) -> c_ulong;
+    pub fn ERR_lib_error_string(e: c_ulong) -> *const c_char;
+    pub fn ERR_reason_error_string(e: c_ulong) -> *const c_char;
+    pub fn ERR_peek_last_error() -> c_ulong;
+    pub fn ERR_clear_error();
+}
+
+// memory BIOs used by AsyncSsl
+#[cfg(feature = "tokio")]
+pub const BIO_CTRL_PENDING: c_int = 10;
+#[cfg(feature = "tokio")]
+pub const BIO_C_SET_BUF_MEM_EOF_RETURN: c_int = 130;
+
+#[cfg(feature = "tokio")]
+unsafe extern "C" {
+    pub fn SSL_get_wbio(ssl: *const SSL) -> *mut BIO;
+    pub fn BIO_read(bio: *mut BIO, data: *mut c_void, dlen: c_int) -> c_int;
+    pub fn BIO_write(bio: *mut BIO, data: *const c_void, dlen: c_int) -> c_int;
+}
+
+#[cfg(feature = "tokio")]
+pub unsafe fn BIO_pending(bio: *mut BIO) -> c_int {
+    unsafe { BIO_ctrl(bio, BIO_CTRL_PENDING, 0, core::ptr::null_mut()) as c_int }
+}
+
+#[cfg(feature = "tokio")]
+pub unsafe fn BIO_set_mem_eof_return(bio: *mut BIO, v: c_long) -> c_long {
+    unsafe { BIO_ctrl(bio, BIO_C_SET_BUF_MEM_EOF_RETURN, v, core::ptr::null_mut()) }
 }
 
 // implemented in C macros
@@ -65,6 +447,136 @@ pub unsafe fn SSL_CTX_set_min_proto_version(ctx: *mut SSL_CTX, version: c_long)
     unsafe { SSL_CTX_ctrl(ctx, SSL_CTRL_SET_MIN_PROTO_VERSION, version, core::ptr::null_mut()) }
 }
 
+pub unsafe fn SSL_CTX_set_max_proto_version(ctx: *mut SSL_CTX, version: c_long) -> c_long {
+    unsafe { SSL_CTX_ctrl(ctx, SSL_CTRL_SET_MAX_PROTO_VERSION, version, core::ptr::null_mut()) }
+}
+
+pub unsafe fn SSL_CTX_get_min_proto_version(ctx: *mut SSL_CTX) -> c_long {
+    unsafe { SSL_CTX_ctrl(ctx, SSL_CTRL_GET_MIN_PROTO_VERSION, 0, core::ptr::null_mut()) }
+}
+
+pub unsafe fn SSL_CTX_get_max_proto_version(ctx: *mut SSL_CTX) -> c_long {
+    unsafe { SSL_CTX_ctrl(ctx, SSL_CTRL_GET_MAX_PROTO_VERSION, 0, core::ptr::null_mut()) }
+}
+
+pub unsafe fn SSL_set_min_proto_version(ssl: *mut SSL, version: c_long) -> c_long {
+    unsafe { SSL_ctrl(ssl, SSL_CTRL_SET_MIN_PROTO_VERSION, version, core::ptr::null_mut()) }
+}
+
+pub unsafe fn SSL_set_max_proto_version(ssl: *mut SSL, version: c_long) -> c_long {
+    unsafe { SSL_ctrl(ssl, SSL_CTRL_SET_MAX_PROTO_VERSION, version, core::ptr::null_mut()) }
+}
+
+pub unsafe fn SSL_get_min_proto_version(ssl: *mut SSL) -> c_long {
+    unsafe { SSL_ctrl(ssl, SSL_CTRL_GET_MIN_PROTO_VERSION, 0, core::ptr::null_mut()) }
+}
+
+pub unsafe fn SSL_get_max_proto_version(ssl: *mut SSL) -> c_long {
+    unsafe { SSL_ctrl(ssl, SSL_CTRL_GET_MAX_PROTO_VERSION, 0, core::ptr::null_mut()) }
+}
+
+pub unsafe fn SSL_CTX_set_session_cache_mode(ctx: *mut SSL_CTX, mode: c_long) -> c_long {
+    unsafe { SSL_CTX_ctrl(ctx, SSL_CTRL_SET_SESS_CACHE_MODE, mode, core::ptr::null_mut()) }
+}
+
+pub unsafe fn SSL_CTX_get_session_cache_mode(ctx: *mut SSL_CTX) -> c_long {
+    unsafe { SSL_CTX_ctrl(ctx, SSL_CTRL_GET_SESS_CACHE_MODE, 0, core::ptr::null_mut()) }
+}
+
+pub unsafe fn SSL_CTX_sess_set_cache_size(ctx: *mut SSL_CTX, size: c_long) -> c_long {
+    unsafe { SSL_CTX_ctrl(ctx, SSL_CTRL_SET_SESS_CACHE_SIZE, size, core::ptr::null_mut()) }
+}
+
+pub unsafe fn SSL_CTX_set1_groups_list(ctx: *mut SSL_CTX, s: *const c_char) -> c_long {
+    unsafe { SSL_CTX_ctrl(ctx, SSL_CTRL_SET_GROUPS_LIST, 0, s as *mut c_void) }
+}
+
+pub unsafe fn SSL_set1_groups_list(ssl: *mut SSL, s: *const c_char) -> c_long {
+    unsafe { SSL_ctrl(ssl, SSL_CTRL_SET_GROUPS_LIST, 0, s as *mut c_void) }
+}
+
+pub unsafe fn SSL_CTX_set1_sigalgs_list(ctx: *mut SSL_CTX, s: *const c_char) -> c_long {
+    unsafe { SSL_CTX_ctrl(ctx, SSL_CTRL_SET_SIGALGS_LIST, 0, s as *mut c_void) }
+}
+
+pub unsafe fn SSL_set1_sigalgs_list(ssl: *mut SSL, s: *const c_char) -> c_long {
+    unsafe { SSL_ctrl(ssl, SSL_CTRL_SET_SIGALGS_LIST, 0, s as *mut c_void) }
+}
+
+pub unsafe fn SSL_CTX_set1_client_sigalgs_list(ctx: *mut SSL_CTX, s: *const c_char) -> c_long {
+    unsafe { SSL_CTX_ctrl(ctx, SSL_CTRL_SET_CLIENT_SIGALGS_LIST, 0, s as *mut c_void) }
+}
+
+pub unsafe fn SSL_set1_client_sigalgs_list(ssl: *mut SSL, s: *const c_char) -> c_long {
+    unsafe { SSL_ctrl(ssl, SSL_CTRL_SET_CLIENT_SIGALGS_LIST, 0, s as *mut c_void) }
+}
+
+pub unsafe fn SSL_get_negotiated_group(ssl: *mut SSL) -> c_int {
+    unsafe { SSL_ctrl(ssl, SSL_CTRL_GET_NEGOTIATED_GROUP, 0, core::ptr::null_mut()) as c_int }
+}
+
+pub unsafe fn SSL_get_peer_signature_nid(ssl: *mut SSL, pnid: *mut c_int) -> c_long {
+    unsafe { SSL_ctrl(ssl, SSL_CTRL_GET_PEER_SIGNATURE_NID, 0, pnid as *mut c_void) }
+}
+
+pub unsafe fn SSL_CTX_set_tlsext_servername_callback(ctx: *mut SSL_CTX, cb: Option<SSL_servername_cb>) -> c_long {
+    let fp = cb.map(|cb| unsafe { core::mem::transmute::<SSL_servername_cb, unsafe extern "C" fn()>(cb) });
+    unsafe { SSL_CTX_callback_ctrl(ctx, SSL_CTRL_SET_TLSEXT_SERVERNAME_CB, fp) }
+}
+
+pub unsafe fn SSL_CTX_clear_chain_certs(ctx: *mut SSL_CTX) -> c_long {
+    unsafe { SSL_CTX_ctrl(ctx, SSL_CTRL_CHAIN, 0, core::ptr::null_mut()) }
+}
+
+pub unsafe fn SSL_CTX_add1_chain_cert(ctx: *mut SSL_CTX, x509: *mut X509) -> c_long {
+    unsafe { SSL_CTX_ctrl(ctx, SSL_CTRL_CHAIN_CERT, 1, x509 as *mut c_void) }
+}
+
+pub unsafe fn SSL_add1_chain_cert(ssl: *mut SSL, x509: *mut X509) -> c_long {
+    unsafe { SSL_ctrl(ssl, SSL_CTRL_CHAIN_CERT, 1, x509 as *mut c_void) }
+}
+
+pub unsafe fn sk_X509_NAME_pop_free(st: *mut OPENSSL_STACK) {
+    unsafe extern "C" fn free(name: *mut c_void) {
+        unsafe { X509_NAME_free(name as *mut X509_NAME) };
+    }
+    unsafe { OPENSSL_sk_pop_free(st, Some(free)) };
+}
+
+pub unsafe fn BIO_get_mem_data(bio: *mut BIO, pp: *mut *mut c_char) -> c_long {
+    unsafe { BIO_ctrl(bio, BIO_CTRL_INFO, 0, pp as *mut c_void) }
+}
+
+pub unsafe fn BIO_clear_retry_flags(bio: *mut BIO) {
+    unsafe { BIO_clear_flags(bio, BIO_FLAGS_RWS | BIO_FLAGS_SHOULD_RETRY) }
+}
+
+pub unsafe fn BIO_set_retry_read(bio: *mut BIO) {
+    unsafe { BIO_set_flags(bio, BIO_FLAGS_READ | BIO_FLAGS_SHOULD_RETRY) }
+}
+
+pub unsafe fn BIO_set_retry_write(bio: *mut BIO) {
+    unsafe { BIO_set_flags(bio, BIO_FLAGS_WRITE | BIO_FLAGS_SHOULD_RETRY) }
+}
+
+pub unsafe fn OPENSSL_free(ptr: *mut c_void) {
+    unsafe { CRYPTO_free(ptr, c"openssl_lite".as_ptr(), 0) }
+}
+
+pub unsafe fn BN_num_bytes(a: *const BIGNUM) -> c_int {
+    (unsafe { BN_num_bits(a) } + 7) / 8
+}
+
+pub fn ERR_GET_LIB(e: c_ulong) -> c_int {
+    if e & 0x80000000 != 0 { return ERR_LIB_SYS; }
+    ((e >> 23) & 0xFF) as c_int
+}
+
+pub fn ERR_GET_REASON(e: c_ulong) -> c_int {
+    if e & 0x80000000 != 0 { return (e & 0x7FFFFFFF) as c_int; }
+    (e & 0x7FFFFF) as c_int
+}
+
 pub unsafe fn SSL_set_tlsext_host_name(ssl: *mut SSL, name: *const c_char) -> c_long {
     unsafe { SSL_ctrl(ssl, SSL_CTRL_SET_TLSEXT_HOSTNAME, TLSEXT_NAMETYPE_host_name, name as *mut c_void) }
 }
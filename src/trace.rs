@@ -0,0 +1,228 @@
+use core::fmt;
+use core::ffi::{c_int, c_long, c_void};
+use core::marker::PhantomData;
+
+use crate::{sys, ex_data};
+use crate::info::str_from_ptr;
+use crate::sni::original_ctx;
+use crate::ssl::catch_callback;
+use crate::{TlsVersion, VerifyError};
+
+type InfoFn = dyn Fn(&InfoEvent) + Send + Sync;
+pub(crate) struct InfoCallback(Box<InfoFn>);
+
+type MsgFn = dyn Fn(&Message) + Send + Sync;
+pub(crate) struct MsgCallback(Box<MsgFn>);
+
+/// State change of a connection, passed to the info callback
+pub struct InfoEvent<'a> {
+    ssl: *const sys::SSL,
+    where_: c_int,
+    ret: c_int,
+    _marker: PhantomData<&'a sys::SSL>,
+}
+
+impl InfoEvent<'_> {
+    /// The handshake has started, also called on renegotiation and TLS 1.3 key updates
+    pub fn is_handshake_start(&self) -> bool {
+        self.where_ & sys::SSL_CB_HANDSHAKE_START != 0
+    }
+
+    /// The handshake has completed
+    pub fn is_handshake_done(&self) -> bool {
+        self.where_ & sys::SSL_CB_HANDSHAKE_DONE != 0
+    }
+
+    /// The handshake moved to the next state, check [`InfoEvent::state`]
+    pub fn is_state_change(&self) -> bool {
+        self.where_ & sys::SSL_CB_LOOP != 0
+    }
+
+    /// `SSL_connect` or `SSL_accept` has returned [`InfoEvent::ret`]
+    pub fn is_exit(&self) -> bool {
+        self.where_ & sys::SSL_CB_EXIT != 0
+    }
+
+    /// Returns true on the server side of the connection
+    #[doc(alias = "SSL_is_server")]
+    pub fn is_server(&self) -> bool {
+        unsafe { sys::SSL_is_server(self.ssl) == 1 }
+    }
+
+    /// Current state of the handshake, e.g. `SSLv3/TLS write client hello`
+    #[doc(alias = "SSL_state_string_long")]
+    pub fn state(&self) -> &str {
+        unsafe { str_from_ptr(sys::SSL_state_string_long(self.ssl)) }.unwrap_or("unknown state")
+    }
+
+    /// Returns the alert that was sent or received
+    pub fn alert(&self) -> Option<Alert> {
+        if self.where_ & sys::SSL_CB_ALERT == 0 { return None; }
+        Some(Alert {
+            received: self.where_ & sys::SSL_CB_READ != 0,
+            fatal: self.ret >> 8 == 2,
+            code: self.ret as u8,
+            description: unsafe { str_from_ptr(sys::SSL_alert_desc_string_long(self.ret)) }.unwrap_or("unknown"),
+        })
+    }
+
+    /// Raw value of the event: return value for exits, alert for alerts
+    pub fn ret(&self) -> i32 {
+        self.ret
+    }
+}
+
+impl fmt::Debug for InfoEvent<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InfoEvent")
+            .field("where", &format_args!("{:#x}", self.where_))
+            .field("state", &self.state())
+            .field("alert", &self.alert())
+            .finish()
+    }
+}
+
+/// TLS alert, reported by [`InfoEvent::alert`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Alert {
+    /// True if the alert came from the peer, false if it was sent
+    pub received: bool,
+    /// Fatal alerts abort the connection, others are warnings (like `close_notify`)
+    pub fatal: bool,
+    /// Alert number, e.g. 42 for `bad_certificate`
+    pub code: u8,
+    /// Description, e.g. `close notify` or `bad certificate`
+    pub description: &'static str,
+}
+
+/// Protocol message, passed to the message callback
+pub struct Message<'a> {
+    sent: bool,
+    version: c_int,
+    content_type: c_int,
+    data: &'a [u8],
+}
+
+impl Message<'_> {
+    /// True if the message was sent, false if it was received
+    pub fn is_sent(&self) -> bool {
+        self.sent
+    }
+
+    /// Protocol version passed by OpenSSL
+    ///
+    /// For record headers, it is the legacy version field on the wire: TLS 1.0 for the first client hello
+    /// and TLS 1.2 for TLS 1.3 records. For other messages, it is usually the version of the connection
+    pub fn version(&self) -> Option<TlsVersion> {
+        TlsVersion::from_raw(self.version as c_long)
+    }
+
+    /// Type of the message
+    pub fn content_type(&self) -> ContentType {
+        ContentType::from_raw(self.content_type)
+    }
+
+    /// Raw message, e.g. the handshake message starting with its type
+    pub fn data(&self) -> &[u8] {
+        self.data
+    }
+}
+
+impl fmt::Debug for Message<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Message")
+            .field("sent", &self.sent)
+            .field("version", &self.version())
+            .field("content_type", &self.content_type())
+            .field("len", &self.data.len())
+            .finish()
+    }
+}
+
+/// Type of a [`Message`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ContentType {
+    ChangeCipherSpec,
+    Alert,
+    Handshake,
+    ApplicationData,
+    /// 5-byte header of a record
+    RecordHeader,
+    /// Real type of a TLS 1.3 encrypted record
+    InnerContentType,
+    Other(i32),
+}
+
+impl ContentType {
+    fn from_raw(content_type: c_int) -> ContentType {
+        match content_type {
+            sys::SSL3_RT_CHANGE_CIPHER_SPEC => ContentType::ChangeCipherSpec,
+            sys::SSL3_RT_ALERT => ContentType::Alert,
+            sys::SSL3_RT_HANDSHAKE => ContentType::Handshake,
+            sys::SSL3_RT_APPLICATION_DATA => ContentType::ApplicationData,
+            sys::SSL3_RT_HEADER => ContentType::RecordHeader,
+            sys::SSL3_RT_INNER_CONTENT_TYPE => ContentType::InnerContentType,
+            other => ContentType::Other(other),
+        }
+    }
+}
+
+impl InfoCallback {
+    pub(crate) fn new<F>(callback: F) -> InfoCallback
+    where F: Fn(&InfoEvent) + Send + Sync + 'static {
+        InfoCallback(Box::new(callback))
+    }
+}
+
+impl MsgCallback {
+    pub(crate) fn new<F>(callback: F) -> MsgCallback
+    where F: Fn(&Message) + Send + Sync + 'static {
+        MsgCallback(Box::new(callback))
+    }
+}
+
+pub(crate) unsafe extern "C" fn info_trampoline(ssl: *const sys::SSL, where_: c_int, ret: c_int) {
+    let event = InfoEvent { ssl, where_, ret, _marker: PhantomData };
+    if event.is_handshake_start() {
+        // Only failures of the current handshake are reported by SslError::Verify
+        drop(unsafe { ex_data::ssl_take::<VerifyError>(ssl.cast_mut()) });
+    }
+    #[cfg(feature = "tracing")]
+    trace_event(&event);
+
+    let ctx = unsafe { sys::SSL_get_SSL_CTX(ssl) };
+    if let Some(callback) = unsafe { ex_data::ctx_get::<InfoCallback>(ctx) } {
+        // Nothing to fail here, the panic is resumed when the SSL call returns
+        unsafe { catch_callback(ssl, || (callback.0)(&event)) };
+    }
+}
+
+pub(crate) unsafe extern "C" fn msg_trampoline(write_p: c_int, version: c_int, content_type: c_int, buf: *const c_void, len: usize, ssl: *mut sys::SSL, _arg: *mut c_void) {
+    // Like the verify callback, the message callback is copied into the SSL object
+    let ctx = unsafe { original_ctx(ssl) };
+    let Some(callback) = (unsafe { ex_data::ctx_get::<MsgCallback>(ctx) }) else { return };
+
+    let data = if len == 0 { &[][..] } else { unsafe { core::slice::from_raw_parts(buf.cast(), len) } };
+    let message = Message { sent: write_p == 1, version, content_type, data };
+    unsafe { catch_callback(ssl, || (callback.0)(&message)) };
+}
+
+#[cfg(feature = "tracing")]
+fn trace_event(event: &InfoEvent) {
+    if event.is_handshake_start() {
+        tracing::debug!(server = event.is_server(), "TLS handshake started");
+    }
+    if event.is_handshake_done() {
+        tracing::debug!(server = event.is_server(), "TLS handshake done");
+    }
+    if event.is_state_change() {
+        tracing::trace!(state = event.state(), "TLS state change");
+    }
+    if let Some(alert) = event.alert() {
+        if alert.received {
+            tracing::debug!(fatal = alert.fatal, code = alert.code, description = alert.description, "TLS alert received");
+        } else {
+            tracing::debug!(fatal = alert.fatal, code = alert.code, description = alert.description, "TLS alert sent");
+        }
+    }
+}
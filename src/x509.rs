@@ -0,0 +1,601 @@
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::ffi::{CStr, c_char, c_int, c_long, c_void};
+use core::marker::PhantomData;
+use core::ops::Deref;
+use std::borrow::Borrow;
+use std::net::IpAddr;
+use std::time::{Duration, SystemTime};
+
+use crate::sys;
+use crate::{ErrorStack, PKey};
+use crate::bio::{MemBio, MemBioSlice};
+
+/// X.509 certificate
+///
+/// Most getters are available through [`X509Ref`]
+pub struct X509(pub(crate) *mut sys::X509);
+
+// X509 objects are reference counted and immutable after parsing
+unsafe impl Send for X509 {}
+unsafe impl Sync for X509 {}
+
+impl X509 {
+    /// Parses a single PEM-encoded certificate
+    #[doc(alias = "PEM_read_bio_X509")]
+    pub fn from_pem(pem: &[u8]) -> Result<X509, ErrorStack> {
+        let bio = MemBioSlice::new(pem)?;
+        let ptr = unsafe { sys::PEM_read_bio_X509(bio.as_ptr(), core::ptr::null_mut(), core::ptr::null(), core::ptr::null_mut()) };
+        if ptr.is_null() { return Err(ErrorStack::get()); }
+        Ok(X509(ptr))
+    }
+
+    /// Parses all PEM-encoded certificates from the buffer, e.g. a leaf with its intermediates
+    pub fn stack_from_pem(pem: &[u8]) -> Result<Vec<X509>, ErrorStack> {
+        let bio = MemBioSlice::new(pem)?;
+        let mut certs = vec![];
+        loop {
+            let ptr = unsafe { sys::PEM_read_bio_X509(bio.as_ptr(), core::ptr::null_mut(), core::ptr::null(), core::ptr::null_mut()) };
+            if ptr.is_null() { break; }
+            certs.push(X509(ptr));
+        }
+
+        // Reaching the end of the buffer is reported as "no start line"
+        let err = unsafe { sys::ERR_peek_last_error() };
+        if !certs.is_empty() && sys::ERR_GET_LIB(err) == sys::ERR_LIB_PEM && sys::ERR_GET_REASON(err) == sys::PEM_R_NO_START_LINE {
+            ErrorStack::clear();
+            return Ok(certs);
+        }
+        Err(ErrorStack::get())
+    }
+
+    /// Parses a DER-encoded certificate
+    #[doc(alias = "d2i_X509")]
+    pub fn from_der(der: &[u8]) -> Result<X509, ErrorStack> {
+        let len = c_long::try_from(der.len()).expect("buffer too large");
+        let mut p = der.as_ptr();
+        let ptr = unsafe { sys::d2i_X509(core::ptr::null_mut(), &mut p, len) };
+        if ptr.is_null() { return Err(ErrorStack::get()); }
+        Ok(X509(ptr))
+    }
+}
+
+impl Deref for X509 {
+    type Target = X509Ref;
+    fn deref(&self) -> &X509Ref {
+        unsafe { X509Ref::from_ptr(self.0) }
+    }
+}
+
+impl AsRef<X509Ref> for X509 {
+    fn as_ref(&self) -> &X509Ref {
+        self
+    }
+}
+
+impl Borrow<X509Ref> for X509 {
+    fn borrow(&self) -> &X509Ref {
+        self
+    }
+}
+
+impl fmt::Debug for X509 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl Clone for X509 {
+    fn clone(&self) -> X509 {
+        unsafe { sys::X509_up_ref(self.0) };
+        X509(self.0)
+    }
+}
+
+impl Drop for X509 {
+    fn drop(&mut self) {
+        unsafe { sys::X509_free(self.0) };
+    }
+}
+
+/// Borrowed X.509 certificate, e.g. from the certificate chain
+///
+/// Call `to_owned()` to keep it longer
+#[repr(transparent)]
+pub struct X509Ref(sys::X509);
+
+impl X509Ref {
+    pub(crate) unsafe fn from_ptr<'a>(ptr: *mut sys::X509) -> &'a X509Ref {
+        unsafe { &*(ptr as *const X509Ref) }
+    }
+
+    pub(crate) fn as_ptr(&self) -> *mut sys::X509 {
+        self as *const X509Ref as *mut sys::X509
+    }
+
+    /// Subject name, i.e. who the certificate belongs to
+    #[doc(alias = "X509_get_subject_name")]
+    pub fn subject_name(&self) -> &X509NameRef {
+        unsafe { X509NameRef::from_ptr(sys::X509_get_subject_name(self.as_ptr())) }
+    }
+
+    /// Issuer name, i.e. who signed the certificate
+    #[doc(alias = "X509_get_issuer_name")]
+    pub fn issuer_name(&self) -> &X509NameRef {
+        unsafe { X509NameRef::from_ptr(sys::X509_get_issuer_name(self.as_ptr())) }
+    }
+
+    /// Serial number as big-endian two's complement bytes, as encoded in the certificate
+    ///
+    /// The shortest encoding is returned, so serial 0 is `[0]` and a leading `0x00` is kept for positive
+    /// serials with the high bit set. Some CAs have issued negative serials, those start with a set high bit
+    #[doc(alias = "X509_get0_serialNumber")]
+    pub fn serial_number(&self) -> Result<Vec<u8>, ErrorStack> {
+        ErrorStack::clear();
+        let (mut buf, negative) = unsafe {
+            let bn = sys::ASN1_INTEGER_to_BN(sys::X509_get0_serialNumber(self.as_ptr()), core::ptr::null_mut());
+            if bn.is_null() { return Err(ErrorStack::get()); }
+            let mut buf = vec![0; sys::BN_num_bytes(bn) as usize];
+            sys::BN_bn2bin(bn, buf.as_mut_ptr());
+            let negative = sys::BN_is_negative(bn) == 1;
+            sys::BN_free(bn);
+            (buf, negative)
+        };
+        if negative {
+            // Negate the magnitude: invert and add one, starting from the last byte
+            let mut carry = true;
+            for byte in buf.iter_mut().rev() {
+                (*byte, carry) = (!*byte).overflowing_add(carry as u8);
+            }
+            if buf.first().is_none_or(|&b| b & 0x80 == 0) { buf.insert(0, 0xff); }
+        } else if buf.first().is_none_or(|&b| b & 0x80 != 0) {
+            buf.insert(0, 0);
+        }
+        Ok(buf)
+    }
+
+    /// Start of the validity period
+    ///
+    /// `None` if the time in the certificate cannot be parsed
+    #[doc(alias = "X509_get0_notBefore")]
+    pub fn not_before(&self) -> Option<SystemTime> {
+        asn1_time_to_system(unsafe { sys::X509_get0_notBefore(self.as_ptr()) })
+    }
+
+    /// End of the validity period
+    ///
+    /// `None` if the time in the certificate cannot be parsed
+    #[doc(alias = "X509_get0_notAfter")]
+    pub fn not_after(&self) -> Option<SystemTime> {
+        asn1_time_to_system(unsafe { sys::X509_get0_notAfter(self.as_ptr()) })
+    }
+
+    /// Public key of the subject
+    #[doc(alias = "X509_get_pubkey")]
+    pub fn public_key(&self) -> Result<PKey, ErrorStack> {
+        let ptr = unsafe { sys::X509_get_pubkey(self.as_ptr()) };
+        if ptr.is_null() { return Err(ErrorStack::get()); }
+        Ok(PKey(ptr))
+    }
+
+    /// Short name of the signature algorithm, e.g. `ecdsa-with-SHA256`
+    #[doc(alias = "X509_get_signature_nid")]
+    pub fn signature_algorithm(&self) -> Option<&'static str> {
+        let nid = unsafe { sys::X509_get_signature_nid(self.as_ptr()) };
+        nid2sn(nid)
+    }
+
+    /// Entries of the Subject Alternative Name extension. Unsupported entry types are skipped
+    pub fn subject_alt_names(&self) -> Vec<AltName> {
+        let names = unsafe { sys::X509_get_ext_d2i(self.as_ptr(), sys::NID_subject_alt_name, core::ptr::null_mut(), core::ptr::null_mut()) } as *mut sys::OPENSSL_STACK;
+        if names.is_null() {
+            // Missing extension leaves nothing in the queue, but a malformed one does
+            ErrorStack::clear();
+            return vec![];
+        }
+
+        let num = unsafe { sys::OPENSSL_sk_num(names) };
+        let mut alt_names = vec![];
+        for i in 0..num {
+            let name = unsafe { sys::OPENSSL_sk_value(names, i) } as *const sys::GENERAL_NAME;
+            let mut ty = 0;
+            let value = unsafe { sys::GENERAL_NAME_get0_value(name, &mut ty) } as *const sys::ASN1_STRING;
+            let data = unsafe { asn1_string_bytes(value) };
+            let alt_name = match ty {
+                sys::GEN_DNS => AltName::Dns(String::from_utf8_lossy(data).into_owned()),
+                sys::GEN_URI => AltName::Uri(String::from_utf8_lossy(data).into_owned()),
+                sys::GEN_EMAIL => AltName::Email(String::from_utf8_lossy(data).into_owned()),
+                sys::GEN_IPADD => match data.len() {
+                    4 => AltName::Ip(IpAddr::from(<[u8; 4]>::try_from(data).unwrap())),
+                    16 => AltName::Ip(IpAddr::from(<[u8; 16]>::try_from(data).unwrap())),
+                    _ => continue,
+                },
+                _ => continue,
+            };
+            alt_names.push(alt_name);
+        }
+        unsafe { sys::GENERAL_NAMES_free(names) };
+        alt_names
+    }
+
+    /// Digest of the DER encoding. Accepts digest names like `c"SHA256"` or `c"SHA1"`
+    #[doc(alias = "X509_digest")]
+    pub fn fingerprint(&self, digest: &CStr) -> Result<Vec<u8>, ErrorStack> {
+        let md = unsafe { sys::EVP_get_digestbyname(digest.as_ptr()) };
+        if md.is_null() { return Err(ErrorStack::custom(format!("unknown digest {digest:?}"))); }
+
+        let mut buf = [0u8; sys::EVP_MAX_MD_SIZE];
+        let mut len = 0;
+        let ret = unsafe { sys::X509_digest(self.as_ptr(), md, buf.as_mut_ptr(), &mut len) };
+        if ret == 0 { return Err(ErrorStack::get()); }
+        Ok(buf[..len as usize].to_vec())
+    }
+
+    /// Encodes the certificate as PEM
+    #[doc(alias = "PEM_write_bio_X509")]
+    pub fn to_pem(&self) -> Result<Vec<u8>, ErrorStack> {
+        let bio = MemBio::new()?;
+        let ret = unsafe { sys::PEM_write_bio_X509(bio.as_ptr(), self.as_ptr()) };
+        if ret == 0 { return Err(ErrorStack::get()); }
+        Ok(bio.get_buf().to_vec())
+    }
+
+    /// Encodes the certificate as DER
+    #[doc(alias = "i2d_X509")]
+    pub fn to_der(&self) -> Result<Vec<u8>, ErrorStack> {
+        let len = unsafe { sys::i2d_X509(self.as_ptr(), core::ptr::null_mut()) };
+        if len <= 0 { return Err(ErrorStack::get()); }
+
+        let mut buf = vec![0u8; len as usize];
+        let mut p = buf.as_mut_ptr();
+        let len = unsafe { sys::i2d_X509(self.as_ptr(), &mut p) };
+        if len <= 0 { return Err(ErrorStack::get()); }
+        buf.truncate(len as usize);
+        Ok(buf)
+    }
+}
+
+impl ToOwned for X509Ref {
+    type Owned = X509;
+    fn to_owned(&self) -> X509 {
+        unsafe { sys::X509_up_ref(self.as_ptr()) };
+        X509(self.as_ptr())
+    }
+}
+
+impl fmt::Debug for X509Ref {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("X509Ref")
+            .field("subject", &self.subject_name())
+            .field("issuer", &self.issuer_name())
+            .finish()
+    }
+}
+
+/// Entry of the Subject Alternative Name extension
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AltName {
+    /// `dNSName`
+    Dns(String),
+    /// `iPAddress`
+    Ip(IpAddr),
+    /// `uniformResourceIdentifier`, e.g. a SPIFFE ID
+    Uri(String),
+    /// `rfc822Name`
+    Email(String),
+}
+
+/// Borrowed distinguished name, e.g. the certificate subject
+///
+/// Displayed like `O=openssl_lite, CN=localhost`
+#[repr(transparent)]
+pub struct X509NameRef(sys::X509_NAME);
+
+impl X509NameRef {
+    pub(crate) unsafe fn from_ptr<'a>(ptr: *mut sys::X509_NAME) -> &'a X509NameRef {
+        unsafe { &*(ptr as *const X509NameRef) }
+    }
+
+    fn as_ptr(&self) -> *mut sys::X509_NAME {
+        self as *const X509NameRef as *mut sys::X509_NAME
+    }
+
+    /// All entries as (field, value) pairs in order, e.g. `("CN", "localhost")`
+    ///
+    /// Unknown fields are named by their OID
+    pub fn entries(&self) -> Vec<(String, String)> {
+        let count = unsafe { sys::X509_NAME_entry_count(self.as_ptr()) };
+        (0..count).map(|i| unsafe {
+            let entry = sys::X509_NAME_get_entry(self.as_ptr(), i);
+            let obj = sys::X509_NAME_ENTRY_get_object(entry);
+            let field = match nid2sn(sys::OBJ_obj2nid(obj)) {
+                Some(sn) => sn.to_string(),
+                None => {
+                    let mut buf = [0u8; 128];
+                    sys::OBJ_obj2txt(buf.as_mut_ptr() as *mut c_char, buf.len() as c_int, obj, 1);
+                    CStr::from_bytes_until_nul(&buf).map(|s| s.to_string_lossy().into_owned()).unwrap_or_default()
+                }
+            };
+            (field, asn1_string_to_utf8(sys::X509_NAME_ENTRY_get_data(entry)))
+        }).collect()
+    }
+
+    /// Value of the last `CN` entry
+    pub fn common_name(&self) -> Option<String> {
+        let mut last = -1;
+        loop {
+            let idx = unsafe { sys::X509_NAME_get_index_by_NID(self.as_ptr(), sys::NID_commonName, last) };
+            if idx < 0 { break; }
+            last = idx;
+        }
+        if last < 0 { return None; }
+        let entry = unsafe { sys::X509_NAME_get_entry(self.as_ptr(), last) };
+        Some(unsafe { asn1_string_to_utf8(sys::X509_NAME_ENTRY_get_data(entry)) })
+    }
+}
+
+impl fmt::Display for X509NameRef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let Ok(bio) = MemBio::new() else { return Err(fmt::Error) };
+        let flags = (sys::ASN1_STRFLGS_RFC2253 & !sys::ASN1_STRFLGS_ESC_MSB) | sys::XN_FLAG_SEP_CPLUS_SPC;
+        let ret = unsafe { sys::X509_NAME_print_ex(bio.as_ptr(), self.as_ptr(), 0, flags) };
+        if ret < 0 { return Err(fmt::Error); }
+        f.write_str(&String::from_utf8_lossy(bio.get_buf()))
+    }
+}
+
+impl fmt::Debug for X509NameRef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "\"{self}\"")
+    }
+}
+
+macro_rules! verify_errors {
+    ($($(#[$doc:meta])* $name:ident = $code:literal,)*) => {
+        /// Reason of certificate verification failure (`X509_V_ERR_*`)
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum X509VerifyError {
+            $($(#[$doc])* $name,)*
+            /// Any other `X509_V_ERR_*` code
+            Other(c_int),
+        }
+
+        impl X509VerifyError {
+            /// Converts `X509_V_ERR_*` code, returning `None` for `X509_V_OK`
+            pub fn from_raw(code: c_int) -> Option<X509VerifyError> {
+                match code {
+                    0 => None,
+                    $($code => Some(X509VerifyError::$name),)*
+                    other => Some(X509VerifyError::Other(other)),
+                }
+            }
+
+            /// Returns `X509_V_ERR_*` code
+            pub fn as_raw(self) -> c_int {
+                match self {
+                    $(X509VerifyError::$name => $code,)*
+                    X509VerifyError::Other(code) => code,
+                }
+            }
+        }
+    };
+}
+
+verify_errors! {
+    /// `X509_V_ERR_UNSPECIFIED`
+    Unspecified = 1,
+    /// `X509_V_ERR_UNABLE_TO_GET_ISSUER_CERT`
+    UnableToGetIssuerCert = 2,
+    /// `X509_V_ERR_UNABLE_TO_GET_CRL`
+    UnableToGetCrl = 3,
+    /// `X509_V_ERR_UNABLE_TO_DECRYPT_CERT_SIGNATURE`
+    UnableToDecryptCertSignature = 4,
+    /// `X509_V_ERR_UNABLE_TO_DECODE_ISSUER_PUBLIC_KEY`
+    UnableToDecodeIssuerPublicKey = 6,
+    /// `X509_V_ERR_CERT_SIGNATURE_FAILURE`
+    CertSignatureFailure = 7,
+    /// `X509_V_ERR_CERT_NOT_YET_VALID`
+    CertNotYetValid = 9,
+    /// `X509_V_ERR_CERT_HAS_EXPIRED`
+    CertHasExpired = 10,
+    /// `X509_V_ERR_ERROR_IN_CERT_NOT_BEFORE_FIELD`
+    ErrorInCertNotBeforeField = 13,
+    /// `X509_V_ERR_ERROR_IN_CERT_NOT_AFTER_FIELD`
+    ErrorInCertNotAfterField = 14,
+    /// `X509_V_ERR_OUT_OF_MEM`
+    OutOfMem = 17,
+    /// `X509_V_ERR_DEPTH_ZERO_SELF_SIGNED_CERT`
+    DepthZeroSelfSignedCert = 18,
+    /// `X509_V_ERR_SELF_SIGNED_CERT_IN_CHAIN`
+    SelfSignedCertInChain = 19,
+    /// `X509_V_ERR_UNABLE_TO_GET_ISSUER_CERT_LOCALLY`
+    UnableToGetIssuerCertLocally = 20,
+    /// `X509_V_ERR_UNABLE_TO_VERIFY_LEAF_SIGNATURE`
+    UnableToVerifyLeafSignature = 21,
+    /// `X509_V_ERR_CERT_CHAIN_TOO_LONG`
+    CertChainTooLong = 22,
+    /// `X509_V_ERR_CERT_REVOKED`
+    CertRevoked = 23,
+    /// `X509_V_ERR_PATH_LENGTH_EXCEEDED`
+    PathLengthExceeded = 25,
+    /// `X509_V_ERR_INVALID_PURPOSE`
+    InvalidPurpose = 26,
+    /// `X509_V_ERR_CERT_UNTRUSTED`
+    CertUntrusted = 27,
+    /// `X509_V_ERR_CERT_REJECTED`
+    CertRejected = 28,
+    /// `X509_V_ERR_KEYUSAGE_NO_CERTSIGN`
+    KeyUsageNoCertSign = 32,
+    /// `X509_V_ERR_UNHANDLED_CRITICAL_EXTENSION`
+    UnhandledCriticalExtension = 34,
+    /// `X509_V_ERR_INVALID_NON_CA`
+    InvalidNonCa = 37,
+    /// `X509_V_ERR_INVALID_EXTENSION`
+    InvalidExtension = 41,
+    /// `X509_V_ERR_APPLICATION_VERIFICATION`
+    ApplicationVerification = 50,
+    /// `X509_V_ERR_HOSTNAME_MISMATCH`
+    HostnameMismatch = 62,
+    /// `X509_V_ERR_EMAIL_MISMATCH`
+    EmailMismatch = 63,
+    /// `X509_V_ERR_IP_ADDRESS_MISMATCH`
+    IpAddressMismatch = 64,
+    /// `X509_V_ERR_EE_KEY_TOO_SMALL`
+    EeKeyTooSmall = 66,
+    /// `X509_V_ERR_CA_KEY_TOO_SMALL`
+    CaKeyTooSmall = 67,
+    /// `X509_V_ERR_CA_MD_TOO_WEAK`
+    CaMdTooWeak = 68,
+    /// `X509_V_ERR_INVALID_CA`
+    InvalidCa = 79,
+}
+
+impl X509VerifyError {
+    /// Human-readable description from OpenSSL, e.g. `certificate has expired`
+    #[doc(alias = "X509_verify_cert_error_string")]
+    pub fn description(self) -> &'static str {
+        let ptr = unsafe { sys::X509_verify_cert_error_string(self.as_raw() as c_long) };
+        if ptr.is_null() { return "unknown certificate verification error"; }
+        unsafe { CStr::from_ptr(ptr) }.to_str().unwrap_or("unknown certificate verification error")
+    }
+}
+
+impl fmt::Display for X509VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.description())
+    }
+}
+
+impl std::error::Error for X509VerifyError {}
+
+pub(crate) fn nid2sn(nid: c_int) -> Option<&'static str> {
+    if nid == sys::NID_undef { return None; }
+    let ptr = unsafe { sys::OBJ_nid2sn(nid) };
+    if ptr.is_null() { return None; }
+    unsafe { CStr::from_ptr(ptr) }.to_str().ok()
+}
+
+unsafe fn asn1_string_bytes<'a>(s: *const sys::ASN1_STRING) -> &'a [u8] {
+    let data = unsafe { sys::ASN1_STRING_get0_data(s) };
+    let len = unsafe { sys::ASN1_STRING_length(s) };
+    if data.is_null() || len <= 0 { return &[]; }
+    unsafe { core::slice::from_raw_parts(data, len as usize) }
+}
+
+unsafe fn asn1_string_to_utf8(s: *const sys::ASN1_STRING) -> String {
+    let mut out = core::ptr::null_mut();
+    let len = unsafe { sys::ASN1_STRING_to_UTF8(&mut out, s) };
+    if len < 0 {
+        ErrorStack::clear();
+        return String::from_utf8_lossy(unsafe { asn1_string_bytes(s) }).into_owned();
+    }
+    let value = String::from_utf8_lossy(unsafe { core::slice::from_raw_parts(out, len as usize) }).into_owned();
+    unsafe { sys::OPENSSL_free(out as *mut c_void) };
+    value
+}
+
+fn asn1_time_to_system(time: *const sys::ASN1_TIME) -> Option<SystemTime> {
+    let mut tm = sys::tm::default();
+    if unsafe { sys::ASN1_TIME_to_tm(time, &mut tm) } == 0 {
+        ErrorStack::clear();
+        return None;
+    }
+
+    // Days since the epoch from a civil date, see http://howardhinnant.github.io/date_algorithms.html
+    let (y, m, d) = (tm.tm_year as i64 + 1900, tm.tm_mon as i64 + 1, tm.tm_mday as i64);
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let doy = (153 * ((m + 9) % 12) + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146097 + doe - 719468;
+
+    let secs = days * 86400 + tm.tm_hour as i64 * 3600 + tm.tm_min as i64 * 60 + tm.tm_sec as i64;
+    if secs >= 0 {
+        SystemTime::UNIX_EPOCH.checked_add(Duration::from_secs(secs as u64))
+    } else {
+        SystemTime::UNIX_EPOCH.checked_sub(Duration::from_secs(secs.unsigned_abs()))
+    }
+}
+
+/// Borrows all certificates from `STACK_OF(X509)`
+pub(crate) unsafe fn stack_refs<'a>(stack: *const sys::OPENSSL_STACK) -> Vec<&'a X509Ref> {
+    if stack.is_null() { return vec![]; }
+    let num = unsafe { sys::OPENSSL_sk_num(stack) };
+    (0..num).map(|i| unsafe { X509Ref::from_ptr(sys::OPENSSL_sk_value(stack, i) as *mut sys::X509) }).collect()
+}
+
+/// Borrows the names of a `STACK_OF(X509_NAME)` owned by someone else
+pub(crate) unsafe fn name_stack_refs<'a>(stack: *const sys::OPENSSL_STACK) -> Vec<&'a X509NameRef> {
+    if stack.is_null() { return vec![]; }
+    let num = unsafe { sys::OPENSSL_sk_num(stack) };
+    (0..num).map(|i| unsafe { X509NameRef::from_ptr(sys::OPENSSL_sk_value(stack, i) as *mut sys::X509_NAME) }).collect()
+}
+
+/// State of certificate chain verification, passed to the verify callback
+///
+/// Not `Sync`: the error can be changed through a shared reference
+#[repr(transparent)]
+pub struct X509StoreContextRef(sys::X509_STORE_CTX, PhantomData<UnsafeCell<()>>);
+
+impl X509StoreContextRef {
+    pub(crate) unsafe fn from_ptr<'a>(ptr: *mut sys::X509_STORE_CTX) -> &'a X509StoreContextRef {
+        unsafe { &*(ptr as *const X509StoreContextRef) }
+    }
+
+    fn as_ptr(&self) -> *mut sys::X509_STORE_CTX {
+        self as *const X509StoreContextRef as *mut sys::X509_STORE_CTX
+    }
+
+    /// Certificate currently being verified
+    #[doc(alias = "X509_STORE_CTX_get_current_cert")]
+    pub fn current_cert(&self) -> Option<&X509Ref> {
+        let ptr = unsafe { sys::X509_STORE_CTX_get_current_cert(self.as_ptr()) };
+        if ptr.is_null() { return None; }
+        Some(unsafe { X509Ref::from_ptr(ptr) })
+    }
+
+    /// Depth of the current certificate in the chain, 0 is the peer certificate
+    #[doc(alias = "X509_STORE_CTX_get_error_depth")]
+    pub fn error_depth(&self) -> u32 {
+        unsafe { sys::X509_STORE_CTX_get_error_depth(self.as_ptr()) as u32 }
+    }
+
+    /// Verification error of the current certificate, if any
+    #[doc(alias = "X509_STORE_CTX_get_error")]
+    pub fn error(&self) -> Option<X509VerifyError> {
+        X509VerifyError::from_raw(self.error_code())
+    }
+
+    /// Raw `X509_V_ERR_*` code of the current certificate, 0 (`X509_V_OK`) if there is no error
+    #[doc(alias = "X509_STORE_CTX_get_error")]
+    pub fn error_code(&self) -> c_int {
+        unsafe { sys::X509_STORE_CTX_get_error(self.as_ptr()) }
+    }
+
+    /// Overrides the error reported for the current certificate
+    #[doc(alias = "X509_STORE_CTX_set_error")]
+    pub fn set_error_code(&self, code: c_int) {
+        unsafe { sys::X509_STORE_CTX_set_error(self.as_ptr(), code) };
+    }
+
+    /// Certificate chain built so far, starting from the peer certificate
+    #[doc(alias = "X509_STORE_CTX_get0_chain")]
+    pub fn chain(&self) -> Vec<&X509Ref> {
+        unsafe { stack_refs(sys::X509_STORE_CTX_get0_chain(self.as_ptr())) }
+    }
+
+    /// Raw pointer to the SSL object performing the verification
+    pub(crate) fn ssl(&self) -> *mut sys::SSL {
+        unsafe { sys::X509_STORE_CTX_get_ex_data(self.as_ptr(), sys::SSL_get_ex_data_X509_STORE_CTX_idx()) as *mut sys::SSL }
+    }
+}
+
+impl fmt::Debug for X509StoreContextRef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("X509StoreContextRef")
+            .field("error_depth", &self.error_depth())
+            .field("error", &self.error())
+            .finish()
+    }
+}
@@ -0,0 +1,79 @@
+//! ALPN negotiation over a loopback connection
+
+mod common;
+
+use std::panic::{self, AssertUnwindSafe};
+
+use openssl_lite::{SslCtx, SslError};
+
+use common::{serve, accept, connect, client_ctx, server_ctx, panic_message};
+
+fn alpn_client() -> SslCtx {
+    let mut ctx = client_ctx();
+    ctx.set_alpn_protocols(&[b"h2", b"http/1.1"]).unwrap();
+    ctx
+}
+
+#[test]
+fn server_selects_offered_protocol() {
+    let mut ctx = server_ctx();
+    ctx.set_alpn_select_callback(|offered| {
+        assert_eq!(offered, [&b"h2"[..], b"http/1.1"]);
+        offered.iter().copied().find(|&p| p == b"http/1.1")
+    });
+    let (sock, server) = serve(move |sock| {
+        let stream = accept(&ctx, sock).unwrap();
+        stream.ssl().selected_alpn_protocol().map(<[u8]>::to_vec)
+    });
+
+    let stream = connect(&alpn_client(), c"localhost", sock).unwrap();
+    assert_eq!(stream.ssl().selected_alpn_protocol(), Some(&b"http/1.1"[..]));
+    assert_eq!(server.join().unwrap().as_deref(), Some(&b"http/1.1"[..]));
+}
+
+#[test]
+fn no_protocol_without_agreement() {
+    let mut ctx = server_ctx();
+    ctx.set_alpn_select_callback(|_| None);
+    let (sock, server) = serve(move |sock| accept(&ctx, sock).map(|_| ()));
+
+    let stream = connect(&alpn_client(), c"localhost", sock).unwrap();
+    assert_eq!(stream.ssl().selected_alpn_protocol(), None);
+    server.join().unwrap().unwrap();
+}
+
+#[test]
+fn selecting_unoffered_protocol_fails_the_handshake() {
+    let mut ctx = server_ctx();
+    ctx.set_alpn_select_callback(|_| Some(b"spdy/3"));
+    let (sock, server) = serve(move |sock| accept(&ctx, sock).map(|_| ()));
+
+    match connect(&alpn_client(), c"localhost", sock) {
+        Err(SslError::PeerAlert(alert)) => assert_eq!(alert.description(), "no application protocol"),
+        other => panic!("expected an alert, got {:?}", other.map(|_| ())),
+    }
+    assert!(server.join().unwrap().is_err());
+}
+
+#[test]
+fn invalid_protocol_names_are_rejected() {
+    let mut ctx = SslCtx::new().unwrap();
+    assert!(ctx.set_alpn_protocols(&[b"h2", b""]).is_err());
+    assert!(ctx.set_alpn_protocols(&[&[b'a'; 256]]).is_err());
+    ctx.set_alpn_protocols(&[&[b'a'; 255]]).unwrap();
+}
+
+#[test]
+fn panicking_select_callback_fails_the_handshake() {
+    let mut ctx = server_ctx();
+    ctx.set_alpn_select_callback(|_| panic!("select callback panicked"));
+    let (sock, server) = serve(move |sock| {
+        panic::catch_unwind(AssertUnwindSafe(|| accept(&ctx, sock).map(|_| ()))).map_err(|p| panic_message(&*p).to_string())
+    });
+
+    match connect(&alpn_client(), c"localhost", sock) {
+        Err(SslError::PeerAlert(alert)) => assert_eq!(alert.description(), "no application protocol"),
+        other => panic!("expected an alert, got {:?}", other.map(|_| ())),
+    }
+    assert_eq!(server.join().unwrap().unwrap_err(), "select callback panicked");
+}
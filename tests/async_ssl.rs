@@ -0,0 +1,193 @@
+//! AsyncSsl over in-memory pipes
+#![cfg(feature = "tokio")]
+
+mod common;
+
+use std::io;
+use std::net::Ipv4Addr;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, DuplexStream, ReadBuf, duplex};
+
+use openssl_lite::{AsyncSsl, Ssl, SslError, TlsAcceptor, TlsConnector, X509VerifyError};
+
+use common::{client_ctx, server_ctx, server_ctx_with, chain, LEAF, LEAF_KEY, OTHER, OTHER_KEY};
+
+/// Connected client and server over a pipe with a small buffer, so that both sides often wait for each other
+async fn handshake(server: AsyncSsl<DuplexStream>, client: AsyncSsl<DuplexStream>) -> (Result<AsyncSsl<DuplexStream>, SslError>, Result<AsyncSsl<DuplexStream>, SslError>) {
+    let (mut server, mut client) = (server, client);
+    let (s, c) = tokio::join!(server.accept(), client.connect());
+    (s.map(|()| server), c.map(|()| client))
+}
+
+fn pair() -> (AsyncSsl<DuplexStream>, AsyncSsl<DuplexStream>) {
+    let (a, b) = duplex(512);
+    let server = AsyncSsl::new(&server_ctx(), a).unwrap();
+    let mut ssl = Ssl::new(&client_ctx()).unwrap();
+    ssl.set_hostname(c"localhost").unwrap();
+    (server, AsyncSsl::from_ssl(ssl, b).unwrap())
+}
+
+#[tokio::test]
+async fn transfers_data_over_duplex() {
+    let (server, client) = pair();
+    let (server, client) = handshake(server, client).await;
+    let (mut server, mut client) = (server.unwrap(), client.unwrap());
+    assert_eq!(client.peer_certificate().unwrap().subject_name().common_name().as_deref(), Some("localhost"));
+    assert_eq!(server.servername(), Some("localhost"));
+
+    let data: Vec<u8> = (0..256 * 1024).map(|i| i as u8).collect();
+    let send = async {
+        client.write_all(&data).await.unwrap();
+        client.shutdown().await.unwrap();
+        let mut echoed = vec![];
+        client.read_to_end(&mut echoed).await.unwrap();
+        echoed
+    };
+    let echo = async {
+        let mut received = vec![];
+        server.read_to_end(&mut received).await.unwrap();
+        server.write_all(&received).await.unwrap();
+        server.shutdown().await.unwrap();
+    };
+    let (echoed, ()) = tokio::join!(send, echo);
+    assert!(echoed == data);
+}
+
+#[tokio::test]
+async fn reports_verify_errors() {
+    let (a, b) = duplex(512);
+    let server = AsyncSsl::new(&server_ctx_with(OTHER, OTHER_KEY), a).unwrap();
+    let mut client = AsyncSsl::new(&client_ctx(), b).unwrap();
+    client.set_hostname(c"localhost").unwrap();
+
+    let (server, client) = handshake(server, client).await;
+    assert!(matches!(client.unwrap_err(), SslError::Verify(_)));
+    assert!(matches!(server.unwrap_err(), SslError::PeerAlert(_)));
+}
+
+#[tokio::test]
+async fn verifies_ip_address() {
+    for (ip, ok) in [(Ipv4Addr::new(127, 0, 0, 1), true), (Ipv4Addr::new(127, 0, 0, 2), false)] {
+        let (a, b) = duplex(512);
+        let server = AsyncSsl::new(&server_ctx(), a).unwrap();
+        let mut client = AsyncSsl::new(&client_ctx(), b).unwrap();
+        client.set_verify_ip(ip.into()).unwrap();
+
+        let (server, client) = handshake(server, client).await;
+        if ok {
+            server.unwrap();
+            client.unwrap();
+        } else {
+            match client.unwrap_err() {
+                SslError::Verify(err) => assert_eq!(err.error(), X509VerifyError::IpAddressMismatch),
+                other => panic!("expected a verify error, got {other:?}"),
+            }
+        }
+    }
+}
+
+#[tokio::test]
+async fn connector_and_acceptor() {
+    let (a, b) = duplex(512);
+    let acceptor = TlsAcceptor::from_pem(&chain(LEAF), LEAF_KEY).unwrap();
+    let connector = TlsConnector::from_ctx(client_ctx());
+    let (server, client) = tokio::join!(acceptor.accept_async(a), connector.connect_async("localhost", b));
+    assert_eq!(server.unwrap().servername(), Some("localhost"));
+    assert_eq!(client.unwrap().verify_result(), Ok(()));
+}
+
+#[tokio::test]
+async fn truncated_stream_is_unexpected_eof() {
+    let (mut a, b) = duplex(512);
+    let mut client = AsyncSsl::new(&client_ctx(), b).unwrap();
+    // The peer closes the connection after the client hello
+    let close = async move { a.read_exact(&mut [0]).await.unwrap(); };
+    let (ret, ()) = tokio::join!(client.connect(), close);
+    assert!(matches!(ret.unwrap_err(), SslError::UnexpectedEof));
+}
+
+#[tokio::test]
+async fn shutdown_closes_stream_of_half_open_session() {
+    let (mut a, b) = duplex(512);
+    let mut client = AsyncSsl::new(&client_ctx(), b).unwrap();
+    // Nothing to send before the handshake, but the stream is still shut down
+    client.shutdown().await.unwrap();
+    assert_eq!(a.read_to_end(&mut vec![]).await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn shutdown_bidirectional_keeps_application_data() {
+    let (server, client) = pair();
+    let (server, client) = handshake(server, client).await;
+    let (mut server, mut client) = (server.unwrap(), client.unwrap());
+
+    let close = async {
+        server.write_all(b"late data").await.unwrap();
+        server.shutdown().await.unwrap();
+        assert_eq!(server.read(&mut [0]).await.unwrap(), 0);
+    };
+    let receive = async {
+        assert!(matches!(client.shutdown_bidirectional().await, Err(SslError::Ssl(_))));
+        let mut data = vec![];
+        client.read_to_end(&mut data).await.unwrap();
+        assert_eq!(data, b"late data");
+        client.shutdown_bidirectional().await.unwrap();
+    };
+    tokio::join!(close, receive);
+}
+
+/// Pipe failing the next `failing_writes` writes, whose shutdown is pending on the first poll
+struct Broken {
+    inner: DuplexStream,
+    failing_writes: u32,
+    shutdown_polled: bool,
+}
+
+impl AsyncRead for Broken {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for Broken {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        if self.failing_writes > 0 {
+            self.failing_writes -= 1;
+            return Poll::Ready(Err(io::ErrorKind::BrokenPipe.into()));
+        }
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if !std::mem::replace(&mut self.shutdown_polled, true) {
+            cx.waker().wake_by_ref();
+            return Poll::Pending;
+        }
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+#[tokio::test]
+async fn shutdown_returns_close_notify_error_after_pending_stream_shutdown() {
+    let (a, b) = duplex(512);
+    let mut server = AsyncSsl::new(&server_ctx(), a).unwrap();
+    let mut ssl = Ssl::new(&client_ctx()).unwrap();
+    ssl.set_hostname(c"localhost").unwrap();
+    let mut client = AsyncSsl::from_ssl(ssl, Broken { inner: b, failing_writes: 0, shutdown_polled: false }).unwrap();
+    let (s, c) = tokio::join!(server.accept(), client.connect());
+    s.unwrap();
+    c.unwrap();
+
+    // Fails the write right after close_notify and its retry, the next write would succeed
+    client.get_mut().failing_writes = 2;
+    assert_eq!(client.shutdown().await.unwrap_err().kind(), io::ErrorKind::BrokenPipe);
+    assert!(client.get_ref().shutdown_polled);
+    // The stream was shut down anyway, without sending close_notify again
+    assert_eq!(server.read(&mut [0]).await.unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+}
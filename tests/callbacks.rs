@@ -0,0 +1,174 @@
+//! Callbacks replaced while a handshake of the same context is running them
+
+mod common;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Barrier};
+
+use openssl_lite::{Ssl, SslCtx, SslStream, TicketKey, TicketKeys, TlsVersion};
+
+use common::{serve, accept, connect, client_ctx, server_ctx};
+
+/// Holds the first callback call until the test has replaced the callback
+struct Pause {
+    held: AtomicBool,
+    barrier: Barrier,
+}
+
+impl Pause {
+    fn new() -> Arc<Pause> {
+        Arc::new(Pause { held: AtomicBool::new(false), barrier: Barrier::new(2) })
+    }
+
+    /// Called by the callback
+    fn hold(&self) {
+        if !self.held.swap(true, Ordering::SeqCst) {
+            self.barrier.wait();
+            self.barrier.wait();
+        }
+    }
+}
+
+/// Callback state that must survive the replacement
+fn canary() -> Vec<u8> {
+    vec![0x5a; 64 * 1024]
+}
+
+fn check(canary: &[u8]) {
+    assert!(canary.iter().all(|&b| b == 0x5a), "callback state was freed");
+}
+
+/// Runs a handshake between the contexts, calling `replace` while a callback is held by `pause`
+fn replace_during_handshake(server: &mut SslCtx, client: &mut SslCtx, pause: &Pause, replace: impl FnOnce(&mut SslCtx, &mut SslCtx)) {
+    // Connections don't borrow their context, so it can be changed while they run
+    let server_ssl = Ssl::new(server).unwrap();
+    let mut client_ssl = Ssl::new(client).unwrap();
+    client_ssl.set_hostname(c"localhost").unwrap();
+
+    let (sock, server_thread) = serve(move |sock| SslStream::new(server_ssl, sock).unwrap().accept());
+    let client_thread = std::thread::spawn(move || SslStream::new(client_ssl, sock).unwrap().connect());
+
+    pause.barrier.wait();
+    replace(server, client);
+    pause.barrier.wait();
+
+    client_thread.join().unwrap().unwrap();
+    server_thread.join().unwrap().unwrap();
+}
+
+#[test]
+fn alpn_select_callback() {
+    let (mut server, mut client) = (server_ctx(), client_ctx());
+    client.set_alpn_protocols(&[b"h2"]).unwrap();
+    let pause = Pause::new();
+    server.set_alpn_select_callback({
+        let (pause, canary) = (pause.clone(), canary());
+        move |offered| {
+            pause.hold();
+            check(&canary);
+            offered.first().copied()
+        }
+    });
+    replace_during_handshake(&mut server, &mut client, &pause, |server, _| server.set_alpn_select_callback(|_| None));
+}
+
+#[test]
+fn verify_callback() {
+    let (mut server, mut client) = (server_ctx(), client_ctx());
+    let pause = Pause::new();
+    client.set_verify_callback({
+        let (pause, canary) = (pause.clone(), canary());
+        move |ok, _| {
+            pause.hold();
+            check(&canary);
+            ok
+        }
+    });
+    replace_during_handshake(&mut server, &mut client, &pause, |_, client| client.set_verify_callback(|ok, _| ok));
+}
+
+#[test]
+fn ticket_keys() {
+    let (mut server, mut client) = (server_ctx(), client_ctx());
+    // TLS 1.2 tickets are received during the handshake
+    client.set_max_version(Some(TlsVersion::Tls1_2)).unwrap();
+    client.enable_client_session_cache(8);
+    server.set_ticket_keys(Arc::new(TicketKeys::new(TicketKey::generate().unwrap(), 1))).unwrap();
+    let pause = Pause::new();
+    server.set_info_callback({
+        let pause = pause.clone();
+        move |_| pause.hold()
+    });
+    let keys = Arc::new(TicketKeys::new(TicketKey::generate().unwrap(), 1));
+    replace_during_handshake(&mut server, &mut client, &pause, |server, _| server.set_ticket_keys(keys.clone()).unwrap());
+
+    // The ticket was issued with the replacement keys
+    let mut other = server_ctx();
+    other.set_ticket_keys(keys).unwrap();
+    let (sock, handle) = serve(move |sock| accept(&other, sock).unwrap().ssl().session_reused());
+    assert!(connect(&client, c"localhost", sock).unwrap().ssl().session_reused());
+    assert!(handle.join().unwrap());
+}
+
+#[test]
+fn servername_callback() {
+    let (mut server, mut client) = (server_ctx(), client_ctx());
+    let pause = Pause::new();
+    server.set_servername_callback({
+        let (pause, canary) = (pause.clone(), canary());
+        move |req| {
+            pause.hold();
+            check(&canary);
+            req.servername() == Some("localhost")
+        }
+    });
+    replace_during_handshake(&mut server, &mut client, &pause, |server, _| server.set_servername_callback(|_| true));
+}
+
+#[test]
+fn keylog_callback() {
+    let (mut server, mut client) = (server_ctx(), client_ctx());
+    let pause = Pause::new();
+    client.set_keylog_callback({
+        let (pause, canary) = (pause.clone(), canary());
+        move |_| {
+            pause.hold();
+            check(&canary);
+        }
+    });
+    let path = std::env::temp_dir().join(format!("openssl-lite-keylog-{}", std::process::id()));
+    replace_during_handshake(&mut server, &mut client, &pause, |_, client| client.set_keylog_file(&path).unwrap());
+
+    // Secrets after the first one went to the file
+    let log = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(log.lines().any(|line| line.starts_with("CLIENT_TRAFFIC_SECRET_0 ")), "{log}");
+}
+
+#[test]
+fn info_callback() {
+    let (mut server, mut client) = (server_ctx(), client_ctx());
+    let pause = Pause::new();
+    server.set_info_callback({
+        let (pause, canary) = (pause.clone(), canary());
+        move |_| {
+            pause.hold();
+            check(&canary);
+        }
+    });
+    replace_during_handshake(&mut server, &mut client, &pause, |server, _| server.set_info_callback(|_| ()));
+}
+
+#[test]
+fn msg_callback() {
+    let (mut server, mut client) = (server_ctx(), client_ctx());
+    let pause = Pause::new();
+    client.set_msg_callback({
+        let (pause, canary) = (pause.clone(), canary());
+        move |_| {
+            pause.hold();
+            check(&canary);
+        }
+    });
+    replace_during_handshake(&mut server, &mut client, &pause, |_, client| client.set_msg_callback(|_| ()));
+}
@@ -0,0 +1,106 @@
+//! Loading server certificates and trusted roots from memory and files
+
+mod common;
+
+use std::ffi::CString;
+
+use openssl_lite::{FileType, SslCtx, SslError, VerifyMode, X509, X509VerifyError};
+
+use common::{serve, accept, connect, client_ctx, server_ctx, chain, CA, INTERMEDIATE, LEAF, LEAF_KEY, TENANT, TENANT_KEY};
+
+const INTERMEDIATE_CN: &str = "Test Intermediate";
+
+/// Path of a file in `tests/certs`
+fn cert_file(name: &str) -> CString {
+    CString::new(format!("{}/tests/certs/{name}", env!("CARGO_MANIFEST_DIR"))).unwrap()
+}
+
+/// Returns common names of the chain sent by the server, leaf first
+fn sent_chain(ctx: SslCtx, host: &core::ffi::CStr) -> Vec<String> {
+    sent_chain_to(&client_ctx(), ctx, host)
+}
+
+fn sent_chain_to(client: &SslCtx, ctx: SslCtx, host: &core::ffi::CStr) -> Vec<String> {
+    let (sock, server) = serve(move |sock| accept(&ctx, sock).map(|_| ()));
+    let stream = connect(client, host, sock).unwrap();
+    server.join().unwrap().unwrap();
+    stream.ssl().peer_cert_chain().iter().map(|cert| cert.subject_name().common_name().unwrap()).collect()
+}
+
+#[test]
+fn chain_from_pem() {
+    let mut ctx = SslCtx::new().unwrap();
+    ctx.set_verify_mode(VerifyMode::NONE);
+    ctx.load_certificate_chain_from_pem(&chain(LEAF), LEAF_KEY).unwrap();
+    assert_eq!(sent_chain(ctx, c"localhost"), ["localhost", INTERMEDIATE_CN]);
+}
+
+#[test]
+fn reloading_replaces_the_chain() {
+    let mut ctx = SslCtx::new().unwrap();
+    ctx.set_verify_mode(VerifyMode::NONE);
+    ctx.load_certificate_chain_from_pem(&chain(LEAF), LEAF_KEY).unwrap();
+    ctx.load_certificate_chain_from_pem(&chain(LEAF), LEAF_KEY).unwrap();
+    // Rotation to another certificate
+    ctx.load_certificate_chain_from_pem(&chain(TENANT), TENANT_KEY).unwrap();
+    assert_eq!(sent_chain(ctx, c"tenant.test"), ["tenant.test", INTERMEDIATE_CN]);
+}
+
+#[test]
+fn reloading_der_leaf_drops_the_pem_chain() {
+    let pem_chain = std::env::temp_dir().join(format!("openssl_lite-chain-{}.pem", std::process::id()));
+    std::fs::write(&pem_chain, chain(LEAF)).unwrap();
+    let mut ctx = SslCtx::new().unwrap();
+    ctx.set_verify_mode(VerifyMode::NONE);
+    ctx.load_certificate_chain(&CString::new(pem_chain.to_str().unwrap()).unwrap(), &cert_file("leaf.key")).unwrap();
+    std::fs::remove_file(&pem_chain).unwrap();
+    ctx.load_certificate_chain_with_type(&cert_file("leaf.der"), &cert_file("leaf.key.der"), FileType::Der).unwrap();
+
+    // The client already knows the intermediate, the server only sends the leaf
+    let mut client = client_ctx();
+    client.add_trusted_certificate(&X509::from_pem(INTERMEDIATE).unwrap()).unwrap();
+    assert_eq!(sent_chain_to(&client, ctx, c"localhost"), ["localhost"]);
+}
+
+#[test]
+fn mismatched_key_is_rejected() {
+    let mut ctx = SslCtx::new().unwrap();
+    assert!(ctx.load_certificate_chain_from_pem(&chain(LEAF), TENANT_KEY).is_err());
+    assert!(ctx.load_certificate_chain_from_pem(b"", LEAF_KEY).is_err());
+    assert!(ctx.load_certificate_chain_from_pem(CA, b"not a key").is_err());
+}
+
+/// Connects `client` to the localhost leaf
+fn handshake(client: &SslCtx) -> Result<(), SslError> {
+    let ctx = server_ctx();
+    let (sock, server) = serve(move |sock| accept(&ctx, sock).map(|_| ()));
+    let ret = connect(client, c"localhost", sock).map(|_| ());
+    let _ = server.join().unwrap();
+    ret
+}
+
+#[test]
+fn trusts_root_from_file() {
+    let mut client = SslCtx::new().unwrap();
+    client.load_verify_file(&cert_file("ca.pem")).unwrap();
+    handshake(&client).unwrap();
+    assert!(client.load_verify_file(&cert_file("missing.pem")).is_err());
+}
+
+#[test]
+fn trusts_root_from_hashed_dir() {
+    let mut client = SslCtx::new().unwrap();
+    client.load_verify_dir(&cert_file("hashed")).unwrap();
+    handshake(&client).unwrap();
+}
+
+#[test]
+fn cleared_trust_store_rejects_the_peer() {
+    let mut client = client_ctx();
+    handshake(&client).unwrap();
+    client.clear_trusted_certificates().unwrap();
+    match handshake(&client) {
+        Err(SslError::Verify(err)) => assert_eq!(err.error(), X509VerifyError::UnableToGetIssuerCertLocally),
+        other => panic!("expected a verify error, got {other:?}"),
+    }
+}
@@ -0,0 +1,48 @@
+//! Client certificates (mutual TLS) over loopback connections
+
+mod common;
+
+use openssl_lite::{SslCtx, SslError, VerifyMode, X509};
+
+use common::{serve, accept, connect, client_ctx, server_ctx, chain, CA, CLIENT, CLIENT_KEY};
+
+/// Server requiring a client certificate issued under the test root
+fn mtls_server() -> SslCtx {
+    let ca = X509::from_pem(CA).unwrap();
+    let mut ctx = server_ctx();
+    ctx.set_verify_mode(VerifyMode::PEER | VerifyMode::FAIL_IF_NO_PEER_CERT);
+    ctx.add_trusted_certificate(&ca).unwrap();
+    ctx.set_client_ca_list(&[ca]).unwrap();
+    ctx
+}
+
+#[test]
+fn client_with_certificate_is_accepted() {
+    let ctx = mtls_server();
+    let (sock, server) = serve(move |sock| {
+        let stream = accept(&ctx, sock).unwrap();
+        stream.ssl().peer_certificate().and_then(|cert| cert.subject_name().common_name())
+    });
+
+    let mut client = client_ctx();
+    client.load_certificate_chain_from_pem(&chain(CLIENT), CLIENT_KEY).unwrap();
+    let stream = connect(&client, c"localhost", sock).unwrap();
+    let names: Vec<_> = stream.ssl().client_ca_names().iter().map(|name| name.common_name()).collect();
+    assert_eq!(names, [Some("Test Root".to_string())]);
+    assert_eq!(server.join().unwrap().as_deref(), Some("client"));
+}
+
+#[test]
+fn client_without_certificate_is_rejected() {
+    let ctx = mtls_server();
+    let (sock, server) = serve(move |sock| accept(&ctx, sock).map(|_| ()));
+
+    // TLS 1.3 clients finish the handshake before the server checks their certificate
+    let mut stream = connect(&client_ctx(), c"localhost", sock).unwrap();
+    assert!(server.join().unwrap().is_err());
+    match stream.ssl_read(&mut [0]) {
+        // certificate_required, which OpenSSL has no description for
+        Err(SslError::PeerAlert(alert)) => assert_eq!(alert.code(), 116),
+        other => panic!("expected an alert, got {other:?}"),
+    }
+}
@@ -0,0 +1,84 @@
+//! Test PKI and loopback helpers shared by the integration tests
+//!
+//! `certs/` holds a root CA, an intermediate and leaves signed by it:
+//! `leaf` (localhost, 127.0.0.1), `tenant` (tenant.test), `client` (client auth) and `expired` (localhost, 2020).
+//! `other` is a self-signed localhost certificate nobody trusts,
+//! `zero-serial` and `negative-serial` are self-signed with unusual serial numbers.
+//! `leaf.der` and `leaf.key.der` are the leaf and its key in DER, `hashed/` holds the root named by its subject hash
+#![allow(dead_code)]
+
+use std::net::{TcpListener, TcpStream};
+use std::thread::JoinHandle;
+
+use openssl_lite::{SslCtx, Ssl, SslStream, SslError, VerifyMode, X509};
+
+pub const CA: &[u8] = include_bytes!("../certs/ca.pem");
+pub const INTERMEDIATE: &[u8] = include_bytes!("../certs/int.pem");
+pub const LEAF: &[u8] = include_bytes!("../certs/leaf.pem");
+pub const LEAF_KEY: &[u8] = include_bytes!("../certs/leaf.key");
+pub const TENANT: &[u8] = include_bytes!("../certs/tenant.pem");
+pub const TENANT_KEY: &[u8] = include_bytes!("../certs/tenant.key");
+pub const CLIENT: &[u8] = include_bytes!("../certs/client.pem");
+pub const CLIENT_KEY: &[u8] = include_bytes!("../certs/client.key");
+pub const EXPIRED: &[u8] = include_bytes!("../certs/expired.pem");
+pub const OTHER: &[u8] = include_bytes!("../certs/other.pem");
+pub const OTHER_KEY: &[u8] = include_bytes!("../certs/other.key");
+pub const ZERO_SERIAL: &[u8] = include_bytes!("../certs/zero-serial.pem");
+pub const NEGATIVE_SERIAL: &[u8] = include_bytes!("../certs/negative-serial.pem");
+
+/// Leaf certificate followed by the intermediate, as sent by servers
+pub fn chain(leaf: &[u8]) -> Vec<u8> {
+    [leaf, INTERMEDIATE].concat()
+}
+
+/// Server context presenting `leaf` (with the intermediate) and not requesting client certificates
+pub fn server_ctx_with(leaf: &[u8], key: &[u8]) -> SslCtx {
+    let mut ctx = SslCtx::new().unwrap();
+    ctx.set_verify_mode(VerifyMode::NONE);
+    ctx.load_certificate_chain_from_pem(&chain(leaf), key).unwrap();
+    ctx
+}
+
+/// Server context presenting the localhost leaf
+pub fn server_ctx() -> SslCtx {
+    server_ctx_with(LEAF, LEAF_KEY)
+}
+
+/// Client context trusting only the test root
+pub fn client_ctx() -> SslCtx {
+    let mut ctx = SslCtx::new().unwrap();
+    ctx.add_trusted_certificate(&X509::from_pem(CA).unwrap()).unwrap();
+    ctx
+}
+
+/// Runs `server` on the accepted side of a loopback TCP connection, returns the client side
+pub fn serve<T, F>(server: F) -> (TcpStream, JoinHandle<T>)
+where T: Send + 'static, F: FnOnce(TcpStream) -> T + Send + 'static {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let (sock, _) = listener.accept().unwrap();
+    (client, std::thread::spawn(move || server(sock)))
+}
+
+/// Accepts a TLS connection with a fresh SSL object of `ctx`
+pub fn accept(ctx: &SslCtx, sock: TcpStream) -> Result<SslStream<TcpStream>, SslError> {
+    let mut stream = SslStream::new(Ssl::new(ctx)?, sock)?;
+    stream.accept()?;
+    Ok(stream)
+}
+
+/// Connects to `host` with a fresh SSL object of `ctx`
+pub fn connect(ctx: &SslCtx, host: &core::ffi::CStr, sock: TcpStream) -> Result<SslStream<TcpStream>, SslError> {
+    let mut ssl = Ssl::new(ctx)?;
+    ssl.set_hostname(host)?;
+    let mut stream = SslStream::new(ssl, sock)?;
+    stream.connect()?;
+    Ok(stream)
+}
+
+/// Message of a panic payload caught with `catch_unwind`
+pub fn panic_message(payload: &(dyn std::any::Any + Send)) -> &str {
+    payload.downcast_ref::<&str>().copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("")
+}
@@ -0,0 +1,51 @@
+//! TlsConnector and TlsAcceptor over loopback connections
+
+mod common;
+
+use openssl_lite::{SslError, TlsAcceptor, TlsConnector, X509VerifyError};
+
+use common::{serve, client_ctx, chain, LEAF, LEAF_KEY};
+
+/// Connects to `domain` on an acceptor presenting the localhost leaf, returns the servername the acceptor saw
+fn connect(domain: &str) -> Result<Option<String>, SslError> {
+    let acceptor = TlsAcceptor::from_pem(&chain(LEAF), LEAF_KEY).unwrap();
+    let (sock, server) = serve(move |sock| acceptor.accept(sock).map(|stream| stream.ssl().servername().map(str::to_owned)));
+    let connector = TlsConnector::from_ctx(client_ctx());
+    match connector.connect(domain, sock) {
+        Ok(_) => Ok(server.join().unwrap().unwrap()),
+        Err(err) => {
+            assert!(matches!(server.join().unwrap(), Err(SslError::PeerAlert(_))));
+            Err(err)
+        }
+    }
+}
+
+fn verify_error(domain: &str) -> X509VerifyError {
+    match connect(domain) {
+        Err(SslError::Verify(err)) => err.error(),
+        other => panic!("expected a verify error, got {other:?}"),
+    }
+}
+
+#[test]
+fn connect_sends_sni() {
+    assert_eq!(connect("localhost").unwrap().as_deref(), Some("localhost"));
+}
+
+#[test]
+fn connect_verifies_hostname() {
+    assert_eq!(verify_error("wrong.test"), X509VerifyError::HostnameMismatch);
+}
+
+#[test]
+fn connect_verifies_ip_without_sni() {
+    assert_eq!(connect("127.0.0.1").unwrap(), None);
+    assert_eq!(verify_error("127.0.0.2"), X509VerifyError::IpAddressMismatch);
+    assert_eq!(verify_error("[::1]"), X509VerifyError::IpAddressMismatch);
+}
+
+#[test]
+fn domain_with_nul_is_rejected() {
+    let connector = TlsConnector::from_ctx(client_ctx());
+    assert!(connector.configure("local\0host").is_err());
+}
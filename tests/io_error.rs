@@ -0,0 +1,60 @@
+//! SslError converted to io::Error by the Read and Write implementations
+
+mod common;
+
+use std::io::{self, ErrorKind, Read};
+use std::net::Shutdown;
+
+use openssl_lite::{SslError, VerifyMode, X509};
+
+use common::{serve, accept, connect, client_ctx, server_ctx, CA, OTHER, OTHER_KEY};
+
+#[test]
+fn truncated_stream_is_unexpected_eof() {
+    let ctx = server_ctx();
+    let (sock, server) = serve(move |sock| {
+        let stream = accept(&ctx, sock).unwrap();
+        // Closes the connection without close_notify
+        stream.get_ref().shutdown(Shutdown::Both).unwrap();
+    });
+    let mut stream = connect(&client_ctx(), c"localhost", sock).unwrap();
+    server.join().unwrap();
+
+    let err = stream.read_to_end(&mut vec![]).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+    assert!(matches!(err.get_ref().and_then(|e| e.downcast_ref()), Some(SslError::UnexpectedEof)));
+}
+
+#[test]
+fn peer_alert_is_invalid_data() {
+    let mut ctx = server_ctx();
+    ctx.set_verify_mode(VerifyMode::PEER | VerifyMode::FAIL_IF_NO_PEER_CERT);
+    ctx.add_trusted_certificate(&X509::from_pem(CA).unwrap()).unwrap();
+    let (sock, server) = serve(move |sock| accept(&ctx, sock).map(|_| ()));
+
+    // TLS 1.3 clients finish the handshake before the server checks their certificate
+    let mut client = client_ctx();
+    client.load_certificate_chain_from_pem(OTHER, OTHER_KEY).unwrap();
+    let mut stream = connect(&client, c"localhost", sock).unwrap();
+    assert!(matches!(server.join().unwrap(), Err(SslError::Verify(_))));
+
+    let err = stream.read(&mut [0]).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+    match err.into_inner().map(|e| e.downcast::<SslError>()) {
+        Some(Ok(err)) => match *err {
+            SslError::PeerAlert(alert) => assert_eq!(alert.description(), "unknown CA"),
+            other => panic!("expected an alert, got {other}"),
+        },
+        other => panic!("expected an SslError, got {other:?}"),
+    }
+}
+
+#[test]
+fn conversion_kinds() {
+    assert_eq!(io::Error::from(SslError::ZeroReturn).kind(), ErrorKind::BrokenPipe);
+    assert_eq!(io::Error::from(SslError::WantRead).kind(), ErrorKind::WouldBlock);
+    // Stream errors are returned as is
+    let err = io::Error::from(SslError::Syscall(ErrorKind::ConnectionReset.into()));
+    assert_eq!(err.kind(), ErrorKind::ConnectionReset);
+    assert!(err.get_ref().is_none());
+}
@@ -0,0 +1,66 @@
+//! Key logging over loopback connections
+
+mod common;
+
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+
+use openssl_lite::{SslCtx, TlsVersion};
+
+use common::{serve, accept, connect, client_ctx, server_ctx, panic_message};
+
+fn handshake(client: &SslCtx) {
+    let ctx = server_ctx();
+    let (sock, server) = serve(move |sock| accept(&ctx, sock).map(|_| ()));
+    connect(client, c"localhost", sock).unwrap();
+    server.join().unwrap().unwrap();
+}
+
+fn logged_labels(version: TlsVersion) -> Vec<String> {
+    let lines = Arc::new(Mutex::new(vec![]));
+    let mut client = client_ctx();
+    client.set_max_version(Some(version)).unwrap();
+    client.set_keylog_callback({
+        let lines = lines.clone();
+        move |line| lines.lock().unwrap().push(line.split(' ').next().unwrap().to_owned())
+    });
+    handshake(&client);
+    let lines = lines.lock().unwrap();
+    lines.clone()
+}
+
+#[test]
+fn callback_receives_secrets() {
+    assert_eq!(logged_labels(TlsVersion::Tls1_2), ["CLIENT_RANDOM"]);
+    let tls13 = logged_labels(TlsVersion::Tls1_3);
+    for label in ["CLIENT_HANDSHAKE_TRAFFIC_SECRET", "SERVER_HANDSHAKE_TRAFFIC_SECRET", "CLIENT_TRAFFIC_SECRET_0", "SERVER_TRAFFIC_SECRET_0"] {
+        assert!(tls13.iter().any(|l| l == label), "{label} missing from {tls13:?}");
+    }
+}
+
+#[test]
+fn file_is_appended() {
+    let path = std::env::temp_dir().join(format!("openssl_lite_keylog_{}.txt", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let mut client = client_ctx();
+    client.set_keylog_file(&path).unwrap();
+    handshake(&client);
+    handshake(&client);
+
+    let log = std::fs::read_to_string(&path).unwrap();
+    #[cfg(unix)]
+    let mode = std::os::unix::fs::PermissionsExt::mode(&std::fs::metadata(&path).unwrap().permissions());
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(log.lines().filter(|l| l.starts_with("CLIENT_TRAFFIC_SECRET_0 ")).count(), 2);
+    // Secrets are only readable by the owner
+    #[cfg(unix)]
+    assert_eq!(mode & 0o777, 0o600);
+}
+
+#[test]
+fn panicking_callback_is_resumed() {
+    let mut client = client_ctx();
+    client.set_keylog_callback(|_| panic!("keylog callback panicked"));
+    let payload = panic::catch_unwind(AssertUnwindSafe(|| handshake(&client))).unwrap_err();
+    assert_eq!(panic_message(&*payload), "keylog callback panicked");
+}
@@ -0,0 +1,102 @@
+//! Negotiated parameters over loopback connections
+
+mod common;
+
+use openssl_lite::{SignatureAlgorithm, Ssl, SslCtx, SslStream, TlsVersion};
+
+use common::{serve, accept, connect, client_ctx, server_ctx};
+
+/// Client side of a connection to `ctx`, checking that the server sees the same parameters
+fn connected(ctx: SslCtx, client: &SslCtx) -> SslStream<std::net::TcpStream> {
+    let (sock, server) = serve(move |sock| {
+        let stream = accept(&ctx, sock).unwrap();
+        let ssl = stream.ssl();
+        (ssl.current_cipher().map(|c| c.protocol_id()), ssl.negotiated_group().map(str::to_owned))
+    });
+    let stream = connect(client, c"localhost", sock).unwrap();
+    let ssl = stream.ssl();
+    assert_eq!(server.join().unwrap(), (ssl.current_cipher().map(|c| c.protocol_id()), ssl.negotiated_group().map(str::to_owned)));
+    stream
+}
+
+#[test]
+fn pinned_tls13_parameters() {
+    let mut ctx = server_ctx();
+    ctx.set_ciphersuites(c"TLS_CHACHA20_POLY1305_SHA256").unwrap();
+    ctx.set_groups_list(c"secp384r1").unwrap();
+    let stream = connected(ctx, &client_ctx());
+    let ssl = stream.ssl();
+
+    let cipher = ssl.current_cipher().unwrap();
+    assert_eq!(cipher.standard_name(), Some("TLS_CHACHA20_POLY1305_SHA256"));
+    assert_eq!(cipher.protocol_id(), 0x1303);
+    assert_eq!(cipher.bits(), 256);
+    assert_eq!(ssl.negotiated_group(), Some("secp384r1"));
+    // The leaf has a P-256 key
+    let sigalg = ssl.peer_signature_algorithm().unwrap();
+    assert_eq!(sigalg, SignatureAlgorithm { signature: "ECDSA", digest: Some("SHA256") });
+    assert_eq!(sigalg.to_string(), "ECDSA+SHA256");
+}
+
+#[test]
+fn pinned_tls12_parameters() {
+    let mut ctx = server_ctx();
+    ctx.set_max_version(Some(TlsVersion::Tls1_2)).unwrap();
+    ctx.set_cipher_list(c"ECDHE-ECDSA-AES128-GCM-SHA256").unwrap();
+    ctx.set_groups_list(c"X25519").unwrap();
+    let stream = connected(ctx, &client_ctx());
+    let ssl = stream.ssl();
+
+    let cipher = ssl.current_cipher().unwrap();
+    assert_eq!(cipher.name(), "ECDHE-ECDSA-AES128-GCM-SHA256");
+    assert_eq!(cipher.standard_name(), Some("TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256"));
+    assert_eq!(ssl.negotiated_group(), Some("x25519"));
+    assert_eq!(ssl.peer_signature_algorithm().map(|s| s.signature), Some("ECDSA"));
+}
+
+#[test]
+fn restricted_signature_algorithms() {
+    // TLS 1.3 ties ECDSA digests to the curve, a P-256 key can only sign with SHA-256
+    let mut client = client_ctx();
+    client.set_max_version(Some(TlsVersion::Tls1_2)).unwrap();
+    client.set_sigalgs_list(c"ECDSA+SHA384").unwrap();
+    let stream = connected(server_ctx(), &client);
+    assert_eq!(stream.ssl().peer_signature_algorithm(), Some(SignatureAlgorithm { signature: "ECDSA", digest: Some("SHA384") }));
+
+    // Nothing the leaf key can sign with
+    client.set_sigalgs_list(c"ed25519").unwrap();
+    let ctx = server_ctx();
+    let (sock, server) = serve(move |sock| accept(&ctx, sock).map(|_| ()));
+    assert!(connect(&client, c"localhost", sock).is_err());
+    assert!(server.join().unwrap().is_err());
+}
+
+#[test]
+fn version_bounds() {
+    let mut ctx = SslCtx::new().unwrap();
+    assert_eq!((ctx.min_version(), ctx.max_version()), (Some(TlsVersion::Tls1_2), None));
+    ctx.set_min_version(None).unwrap();
+    ctx.set_max_version(Some(TlsVersion::Tls1_2)).unwrap();
+    assert_eq!((ctx.min_version(), ctx.max_version()), (None, Some(TlsVersion::Tls1_2)));
+
+    // Connections start with the bounds of the context
+    let mut ssl = Ssl::new(&ctx).unwrap();
+    assert_eq!((ssl.min_version(), ssl.max_version()), (None, Some(TlsVersion::Tls1_2)));
+    ssl.set_min_version(Some(TlsVersion::Tls1_3)).unwrap();
+    ssl.set_max_version(None).unwrap();
+    assert_eq!((ssl.min_version(), ssl.max_version()), (Some(TlsVersion::Tls1_3), None));
+    assert_eq!((ctx.min_version(), ctx.max_version()), (None, Some(TlsVersion::Tls1_2)));
+}
+
+#[test]
+fn connection_version_bound_overrides_ctx() {
+    let ctx = server_ctx();
+    let (sock, server) = serve(move |sock| accept(&ctx, sock).unwrap().ssl().version());
+    let mut ssl = Ssl::new(&client_ctx()).unwrap();
+    ssl.set_hostname(c"localhost").unwrap();
+    ssl.set_max_version(Some(TlsVersion::Tls1_2)).unwrap();
+    let mut stream = SslStream::new(ssl, sock).unwrap();
+    stream.connect().unwrap();
+    assert_eq!(stream.ssl().version(), Some(TlsVersion::Tls1_2));
+    assert_eq!(server.join().unwrap(), Some(TlsVersion::Tls1_2));
+}
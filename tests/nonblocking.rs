@@ -0,0 +1,84 @@
+//! Non-blocking handshakes with MidHandshake
+
+mod common;
+
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+use openssl_lite::{HandshakeResult, Ssl, SslCtx, SslError, SslStream};
+
+use common::{serve, client_ctx, server_ctx};
+
+/// Drives the handshake to completion, returns the stream and the number of times it would have blocked
+fn finish(mut result: HandshakeResult<TcpStream>) -> Result<(SslStream<TcpStream>, usize), SslError> {
+    let mut blocked = 0;
+    loop {
+        match result {
+            HandshakeResult::Done(stream) => return Ok((stream, blocked)),
+            HandshakeResult::WouldBlock(mid) => {
+                assert!(mid.get_ref().peer_addr().is_ok());
+                blocked += 1;
+                // A real program would wait for readiness with poll, mio, etc.
+                std::thread::sleep(Duration::from_millis(1));
+                result = mid.handshake()?;
+            }
+        }
+    }
+}
+
+fn nonblocking(ctx: &SslCtx, sock: TcpStream, host: Option<&core::ffi::CStr>) -> SslStream<TcpStream> {
+    sock.set_nonblocking(true).unwrap();
+    let mut ssl = Ssl::new(ctx).unwrap();
+    if let Some(host) = host { ssl.set_hostname(host).unwrap(); }
+    SslStream::new(ssl, sock).unwrap()
+}
+
+#[test]
+fn handshake_resumes_after_would_block() {
+    let ctx = server_ctx();
+    let (sock, server) = serve(move |sock| {
+        let stream = nonblocking(&ctx, sock, None);
+        let (mut stream, blocked) = finish(stream.start_accept().unwrap()).unwrap();
+        stream.get_mut().set_nonblocking(false).unwrap();
+        stream.write_all(b"done").unwrap();
+        blocked
+    });
+
+    let stream = nonblocking(&client_ctx(), sock, Some(c"localhost"));
+    let first = stream.start_connect().unwrap();
+    // The client hello is sent, the server hasn't answered yet
+    match &first {
+        HandshakeResult::WouldBlock(mid) => assert!(mid.wants_read()),
+        HandshakeResult::Done(_) => panic!("handshake can't complete without the server"),
+    }
+    let (mut stream, blocked) = finish(first).unwrap();
+    assert!(blocked >= 1);
+    assert!(stream.ssl().is_handshake_finished());
+
+    // Nothing to read until the server writes
+    let mut buf = [0; 4];
+    let mut read = 0;
+    while read < buf.len() {
+        match stream.read(&mut buf[read..]) {
+            Ok(n) => read += n,
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => std::thread::sleep(Duration::from_millis(1)),
+            Err(err) => panic!("{err}"),
+        }
+    }
+    assert_eq!(&buf, b"done");
+    assert!(server.join().unwrap() >= 1);
+}
+
+#[test]
+fn handshake_errors_are_returned() {
+    let (sock, server) = serve(|sock| {
+        // Not TLS
+        let mut sock = sock;
+        sock.write_all(&[0xff; 64]).unwrap();
+    });
+    let stream = nonblocking(&client_ctx(), sock, Some(c"localhost"));
+    let ret = stream.start_connect().and_then(finish);
+    server.join().unwrap();
+    assert!(matches!(ret.map(|_| ()), Err(SslError::Ssl(_))));
+}
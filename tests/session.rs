@@ -0,0 +1,146 @@
+//! Session resumption over loopback connections
+
+mod common;
+
+use std::io::{Read, Write};
+use std::sync::Arc;
+
+use openssl_lite::{SslCtx, TicketKey, TicketKeys, TlsVersion, VerifyMode, X509};
+
+use common::{serve, accept, connect, client_ctx, server_ctx, server_ctx_with, CA, TENANT, TENANT_KEY};
+
+const VERSIONS: [TlsVersion; 2] = [TlsVersion::Tls1_2, TlsVersion::Tls1_3];
+
+fn caching_client(version: TlsVersion) -> SslCtx {
+    let mut client = client_ctx();
+    client.set_max_version(Some(version)).unwrap();
+    client.enable_client_session_cache(8);
+    client
+}
+
+/// Connects to `host` and reads one byte so that TLS 1.3 tickets get processed, returns whether the session was resumed
+fn resumed(server: &Arc<SslCtx>, client: &SslCtx, host: &core::ffi::CStr) -> bool {
+    let server = server.clone();
+    let (sock, handle) = serve(move |sock| {
+        let mut stream = accept(&server, sock).unwrap();
+        stream.write_all(b"x").unwrap();
+        stream.ssl().session_reused()
+    });
+    let mut stream = connect(client, host, sock).unwrap();
+    stream.read_exact(&mut [0]).unwrap();
+    let reused = stream.ssl().session_reused();
+    assert_eq!(handle.join().unwrap(), reused);
+    reused
+}
+
+#[test]
+fn client_cache_resumes_per_host() {
+    let server = Arc::new(server_ctx());
+    for version in VERSIONS {
+        let client = caching_client(version);
+        assert!(!resumed(&server, &client, c"localhost"));
+        assert!(resumed(&server, &client, c"localhost"), "{version:?}");
+        assert!(resumed(&server, &client, c"localhost"), "{version:?}");
+    }
+}
+
+#[test]
+fn client_cache_evicts_least_recently_used() {
+    let server = Arc::new(server_ctx());
+    for version in VERSIONS {
+        // Hostnames only select the cache entry, the server certificate is not checked
+        let mut client = SslCtx::new().unwrap();
+        client.set_verify_mode(VerifyMode::NONE);
+        client.set_max_version(Some(version)).unwrap();
+        client.enable_client_session_cache(2);
+
+        assert!(!resumed(&server, &client, c"a.test"));
+        assert!(!resumed(&server, &client, c"b.test"));
+        assert!(resumed(&server, &client, c"a.test"));
+        // Evicts b.test, which was used longest ago
+        assert!(!resumed(&server, &client, c"c.test"));
+        assert!(resumed(&server, &client, c"a.test"), "{version:?}");
+        assert!(!resumed(&server, &client, c"b.test"), "{version:?}");
+    }
+}
+
+#[test]
+fn replaced_cache_receives_sessions_of_running_connections() {
+    let server = Arc::new(server_ctx());
+    let mut client = caching_client(TlsVersion::Tls1_3);
+    let (sock, handle) = serve({
+        let server = server.clone();
+        move |sock| accept(&server, sock).unwrap().write_all(b"x").unwrap()
+    });
+    let mut stream = connect(&client, c"localhost", sock).unwrap();
+    // The TLS 1.3 ticket is only processed by the read
+    client.enable_client_session_cache(8);
+    stream.read_exact(&mut [0]).unwrap();
+    handle.join().unwrap();
+    assert!(resumed(&server, &client, c"localhost"));
+}
+
+#[test]
+fn no_cache_without_opt_in() {
+    let server = Arc::new(server_ctx());
+    let client = client_ctx();
+    assert!(!resumed(&server, &client, c"localhost"));
+    assert!(!resumed(&server, &client, c"localhost"));
+}
+
+#[test]
+fn shared_ticket_keys_resume_across_servers() {
+    let keys = Arc::new(TicketKeys::new(TicketKey::generate().unwrap(), 2));
+    let with_keys = || {
+        let mut ctx = server_ctx();
+        ctx.set_ticket_keys(keys.clone()).unwrap();
+        Arc::new(ctx)
+    };
+    let (first, second) = (with_keys(), with_keys());
+    for version in VERSIONS {
+        let client = caching_client(version);
+        assert!(!resumed(&first, &client, c"localhost"));
+        assert!(resumed(&second, &client, c"localhost"), "{version:?}");
+        // Rotated keys still decrypt old tickets
+        keys.rotate(TicketKey::generate().unwrap());
+        assert!(resumed(&first, &client, c"localhost"), "{version:?}");
+    }
+}
+
+#[test]
+fn ticket_keys_of_original_ctx_after_sni_switch() {
+    let tenant = server_ctx_with(TENANT, TENANT_KEY);
+    let mut ctx = server_ctx();
+    ctx.set_ticket_keys(Arc::new(TicketKeys::new(TicketKey::generate().unwrap(), 1))).unwrap();
+    ctx.set_servername_callback(move |req| match req.servername() {
+        Some("tenant.test") => req.set_ctx(&tenant).is_ok(),
+        _ => true,
+    });
+    let server = Arc::new(ctx);
+    for version in VERSIONS {
+        let client = caching_client(version);
+        assert!(!resumed(&server, &client, c"tenant.test"));
+        assert!(resumed(&server, &client, c"tenant.test"), "{version:?}");
+    }
+}
+
+#[test]
+fn verifying_server_resumes_with_session_id_context() {
+    let mut ctx = server_ctx();
+    ctx.set_verify_mode(VerifyMode::PEER);
+    ctx.add_trusted_certificate(&X509::from_pem(CA).unwrap()).unwrap();
+    ctx.set_session_id_context(b"session test").unwrap();
+    let server = Arc::new(ctx);
+    for version in VERSIONS {
+        let client = caching_client(version);
+        assert!(!resumed(&server, &client, c"localhost"));
+        assert!(resumed(&server, &client, c"localhost"), "{version:?}");
+    }
+}
+
+#[test]
+fn session_id_context_length_is_checked() {
+    let mut ctx = SslCtx::new().unwrap();
+    ctx.set_session_id_context(&[b'x'; 32]).unwrap();
+    assert!(ctx.set_session_id_context(&[b'x'; 33]).is_err());
+}
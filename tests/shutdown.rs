@@ -0,0 +1,67 @@
+//! One-way and bidirectional shutdown over loopback connections
+
+mod common;
+
+use std::io::{Read, Write};
+use std::sync::mpsc;
+
+use openssl_lite::{ShutdownState, SslError};
+
+use common::{serve, accept, connect, client_ctx, server_ctx};
+
+#[test]
+fn shutdown_does_not_wait_for_peer() {
+    let ctx = server_ctx();
+    let (tx, rx) = mpsc::channel();
+    let (sock, server) = serve(move |sock| {
+        let mut stream = accept(&ctx, sock).unwrap();
+        rx.recv().unwrap();
+        assert_eq!(stream.read(&mut [0]).unwrap(), 0);
+        assert!(stream.ssl().shutdown_state().contains(ShutdownState::RECEIVED));
+        stream.shutdown().unwrap();
+    });
+    let mut stream = connect(&client_ctx(), c"localhost", sock).unwrap();
+    // Returns while the server is not reading yet
+    stream.shutdown().unwrap();
+    assert_eq!(stream.ssl().shutdown_state(), ShutdownState::SENT);
+    // Sent once only
+    stream.shutdown().unwrap();
+    tx.send(()).unwrap();
+
+    assert_eq!(stream.read(&mut [0]).unwrap(), 0);
+    assert_eq!(stream.ssl().shutdown_state(), ShutdownState::SENT | ShutdownState::RECEIVED);
+    server.join().unwrap();
+}
+
+#[test]
+fn shutdown_bidirectional_waits_for_peer() {
+    let ctx = server_ctx();
+    let (sock, server) = serve(move |sock| {
+        let mut stream = accept(&ctx, sock).unwrap();
+        assert_eq!(stream.read(&mut [0]).unwrap(), 0);
+        stream.shutdown().unwrap();
+    });
+    let mut stream = connect(&client_ctx(), c"localhost", sock).unwrap();
+    stream.shutdown_bidirectional().unwrap();
+    assert_eq!(stream.ssl().shutdown_state(), ShutdownState::SENT | ShutdownState::RECEIVED);
+    server.join().unwrap();
+}
+
+#[test]
+fn shutdown_bidirectional_keeps_application_data() {
+    let ctx = server_ctx();
+    let (sock, server) = serve(move |sock| {
+        let mut stream = accept(&ctx, sock).unwrap();
+        stream.write_all(b"late data").unwrap();
+        stream.shutdown().unwrap();
+        assert_eq!(stream.read(&mut [0]).unwrap(), 0);
+    });
+    let mut stream = connect(&client_ctx(), c"localhost", sock).unwrap();
+    assert!(matches!(stream.shutdown_bidirectional(), Err(SslError::Ssl(_))));
+
+    let mut data = vec![];
+    stream.read_to_end(&mut data).unwrap();
+    assert_eq!(data, b"late data");
+    stream.shutdown_bidirectional().unwrap();
+    server.join().unwrap();
+}
@@ -0,0 +1,117 @@
+//! Server name callback over loopback connections
+
+mod common;
+
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+
+use openssl_lite::{ContentType, PKey, SslCtx, SslError, VerifyMode, X509};
+
+use common::{serve, accept, connect, client_ctx, server_ctx, server_ctx_with, chain, panic_message};
+use common::{CA, CLIENT, CLIENT_KEY, INTERMEDIATE, TENANT, TENANT_KEY};
+
+/// Server context switching to the tenant context for `tenant.test`, and rejecting other names except localhost
+fn switching_ctx(tenant: SslCtx) -> SslCtx {
+    let mut ctx = server_ctx();
+    ctx.set_servername_callback(move |req| match req.servername() {
+        Some("tenant.test") => req.set_ctx(&tenant).is_ok(),
+        Some("localhost") | None => true,
+        Some(_) => false,
+    });
+    ctx
+}
+
+fn peer_cn(ctx: SslCtx, host: &core::ffi::CStr) -> Result<String, SslError> {
+    let (sock, server) = serve(move |sock| accept(&ctx, sock).map(|s| s.ssl().servername().map(str::to_owned)));
+    let stream = connect(&client_ctx(), host, sock)?;
+    assert_eq!(server.join().unwrap().unwrap().as_deref(), Some(host.to_str().unwrap()));
+    Ok(stream.ssl().peer_certificate().unwrap().subject_name().common_name().unwrap())
+}
+
+#[test]
+fn switches_context_by_servername() {
+    assert_eq!(peer_cn(switching_ctx(server_ctx_with(TENANT, TENANT_KEY)), c"tenant.test").unwrap(), "tenant.test");
+    assert_eq!(peer_cn(switching_ctx(server_ctx_with(TENANT, TENANT_KEY)), c"localhost").unwrap(), "localhost");
+}
+
+#[test]
+fn rejects_unknown_servername() {
+    let ctx = switching_ctx(server_ctx_with(TENANT, TENANT_KEY));
+    let (sock, server) = serve(move |sock| accept(&ctx, sock).map(|_| ()));
+    match connect(&client_ctx(), c"unknown.test", sock) {
+        Err(SslError::PeerAlert(alert)) => assert_eq!(alert.description(), "unrecognized name"),
+        other => panic!("expected an alert, got {:?}", other.map(|_| ())),
+    }
+    assert!(server.join().unwrap().is_err());
+}
+
+#[test]
+fn switches_certificate_by_servername() {
+    let tenant = X509::from_pem(TENANT).unwrap();
+    let intermediate = X509::from_pem(INTERMEDIATE).unwrap();
+    let key = PKey::private_key_from_pem(TENANT_KEY).unwrap();
+    let mut ctx = server_ctx();
+    ctx.set_servername_callback(move |req| {
+        if req.servername() == Some("tenant.test") {
+            req.use_certificate(&tenant).unwrap();
+            req.add_chain_certificate(&intermediate).unwrap();
+            req.use_private_key(&key).unwrap();
+        }
+        true
+    });
+    assert_eq!(peer_cn(ctx, c"tenant.test").unwrap(), "tenant.test");
+}
+
+#[test]
+fn verify_and_msg_callbacks_of_original_context_are_kept() {
+    let mut tenant = server_ctx_with(TENANT, TENANT_KEY);
+    // The new context provides the trust store, but not the verify callback
+    tenant.add_trusted_certificate(&X509::from_pem(CA).unwrap()).unwrap();
+    tenant.set_verify_callback(|_, _| false);
+
+    let verified = Arc::new(Mutex::new(vec![]));
+    let certificates_sent = Arc::new(Mutex::new(0));
+    let mut ctx = switching_ctx(tenant);
+    ctx.set_verify_mode(VerifyMode::PEER | VerifyMode::FAIL_IF_NO_PEER_CERT);
+    ctx.set_verify_callback({
+        let verified = verified.clone();
+        move |ok, store| {
+            let cn = store.current_cert().and_then(|cert| cert.subject_name().common_name());
+            verified.lock().unwrap().push((store.error_depth(), cn));
+            ok
+        }
+    });
+    ctx.set_msg_callback({
+        let certificates_sent = certificates_sent.clone();
+        move |msg| {
+            // Certificate handshake message
+            if msg.is_sent() && msg.content_type() == ContentType::Handshake && msg.data().first() == Some(&11) {
+                *certificates_sent.lock().unwrap() += 1;
+            }
+        }
+    });
+
+    let mut client = client_ctx();
+    client.load_certificate_chain_from_pem(&chain(CLIENT), CLIENT_KEY).unwrap();
+    let (sock, server) = serve(move |sock| accept(&ctx, sock).map(|_| ()));
+    connect(&client, c"tenant.test", sock).unwrap();
+    server.join().unwrap().unwrap();
+
+    assert!(verified.lock().unwrap().contains(&(0, Some("client".into()))));
+    assert_eq!(*certificates_sent.lock().unwrap(), 1);
+}
+
+#[test]
+fn panicking_callback_fails_the_handshake() {
+    let mut ctx = server_ctx();
+    ctx.set_servername_callback(|_| panic!("servername callback panicked"));
+    let (sock, server) = serve(move |sock| {
+        panic::catch_unwind(AssertUnwindSafe(|| accept(&ctx, sock).map(|_| ()))).map_err(|p| panic_message(&*p).to_string())
+    });
+
+    match connect(&client_ctx(), c"localhost", sock) {
+        Err(SslError::PeerAlert(alert)) => assert_eq!(alert.description(), "internal error"),
+        other => panic!("expected an alert, got {:?}", other.map(|_| ())),
+    }
+    assert_eq!(server.join().unwrap().unwrap_err(), "servername callback panicked");
+}
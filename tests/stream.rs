@@ -0,0 +1,107 @@
+//! SslStream over custom stream types
+
+mod common;
+
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::panic::{self, AssertUnwindSafe};
+
+use openssl_lite::{Ssl, SslError, SslStream};
+
+use common::{serve, accept, client_ctx, server_ctx, panic_message};
+
+/// TCP stream counting encrypted bytes, optionally failing or panicking on reads
+struct Tap {
+    inner: TcpStream,
+    read: usize,
+    written: usize,
+    fail: Option<fn() -> io::Error>,
+    panic: bool,
+}
+
+impl Tap {
+    fn new(inner: TcpStream) -> Tap {
+        Tap { inner, read: 0, written: 0, fail: None, panic: false }
+    }
+}
+
+impl Read for Tap {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.panic { panic!("stream read panicked"); }
+        if let Some(fail) = self.fail { return Err(fail()); }
+        let n = self.inner.read(buf)?;
+        self.read += n;
+        Ok(n)
+    }
+}
+
+impl Write for Tap {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.written += n;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+fn tap_connect(tap: Tap) -> Result<SslStream<Tap>, SslError> {
+    let mut ssl = Ssl::new(&client_ctx())?;
+    ssl.set_hostname(c"localhost")?;
+    let mut stream = SslStream::new(ssl, tap)?;
+    stream.connect()?;
+    Ok(stream)
+}
+
+#[test]
+fn tls_over_custom_stream() {
+    let ctx = server_ctx();
+    let (sock, server) = serve(move |sock| {
+        let mut stream = accept(&ctx, sock).unwrap();
+        let mut buf = [0; 5];
+        stream.read_exact(&mut buf).unwrap();
+        stream.write_all(&buf).unwrap();
+        stream.send_close_notify().unwrap();
+    });
+
+    let mut stream = tap_connect(Tap::new(sock)).unwrap();
+    stream.write_all(b"hello").unwrap();
+    let mut buf = vec![];
+    stream.read_to_end(&mut buf).unwrap();
+    assert_eq!(buf, b"hello");
+    server.join().unwrap();
+
+    let tap = stream.into_inner();
+    assert!(tap.read > 5 && tap.written > 5);
+}
+
+#[test]
+fn stream_errors_are_returned() {
+    let ctx = server_ctx();
+    let (sock, server) = serve(move |sock| accept(&ctx, sock).map(|_| ()));
+
+    let mut tap = Tap::new(sock);
+    tap.fail = Some(|| io::Error::new(io::ErrorKind::ConnectionReset, "injected"));
+    match tap_connect(tap) {
+        Err(SslError::Syscall(err)) => {
+            assert_eq!(err.kind(), io::ErrorKind::ConnectionReset);
+            assert_eq!(err.to_string(), "injected");
+        }
+        other => panic!("expected a stream error, got {:?}", other.map(|_| ())),
+    }
+    assert!(server.join().unwrap().is_err());
+}
+
+#[test]
+fn stream_panics_are_resumed() {
+    let ctx = server_ctx();
+    let (sock, server) = serve(move |sock| accept(&ctx, sock).map(|_| ()));
+
+    let mut tap = Tap::new(sock);
+    tap.panic = true;
+    let payload = panic::catch_unwind(AssertUnwindSafe(|| tap_connect(tap).map(|_| ()))).unwrap_err();
+    assert_eq!(panic_message(&*payload), "stream read panicked");
+    assert!(server.join().unwrap().is_err());
+}
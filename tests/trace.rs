@@ -0,0 +1,121 @@
+//! Info and message callbacks over loopback connections
+
+mod common;
+
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+
+use openssl_lite::{Alert, ContentType, SslCtx, TlsVersion};
+
+use common::{serve, accept, connect, client_ctx, server_ctx, panic_message};
+
+fn handshake(client: &SslCtx) {
+    let ctx = server_ctx();
+    let (sock, server) = serve(move |sock| accept(&ctx, sock).map(|_| ()));
+    // Dropping the stream sends close_notify
+    drop(connect(client, c"localhost", sock).unwrap());
+    server.join().unwrap().unwrap();
+}
+
+#[test]
+fn info_callback_sees_handshake_and_alerts() {
+    let events = Arc::new(Mutex::new((0, 0, vec![])));
+    let mut client = client_ctx();
+    client.set_info_callback({
+        let events = events.clone();
+        move |event| {
+            let mut events = events.lock().unwrap();
+            assert!(!event.is_server());
+            if event.is_handshake_start() { events.0 += 1; }
+            if event.is_handshake_done() { events.1 += 1; }
+            if let Some(alert) = event.alert() { events.2.push(alert); }
+        }
+    });
+    handshake(&client);
+
+    let (started, done, alerts) = &*events.lock().unwrap();
+    assert_eq!((*started, *done), (1, 1));
+    assert_eq!(alerts, &[Alert { received: false, fatal: false, code: 0, description: "close notify" }]);
+}
+
+#[test]
+fn msg_callback_sees_records() {
+    let messages = Arc::new(Mutex::new(vec![]));
+    let mut client = client_ctx();
+    client.set_max_version(Some(TlsVersion::Tls1_3)).unwrap();
+    client.set_msg_callback({
+        let messages = messages.clone();
+        move |msg| messages.lock().unwrap().push((msg.is_sent(), msg.content_type(), msg.version(), msg.data().to_vec()))
+    });
+    handshake(&client);
+
+    let messages = messages.lock().unwrap();
+    // The client hello is sent in a TLS 1.0 record
+    let (sent, ty, version, header) = &messages[0];
+    assert_eq!((*sent, *ty, *version), (true, ContentType::RecordHeader, Some(TlsVersion::Tls1)));
+    assert_eq!(header[0], 22);
+    let (sent, ty, _, hello) = &messages[1];
+    assert_eq!((*sent, *ty, hello[0]), (true, ContentType::Handshake, 1));
+    // Server certificate, in an encrypted TLS 1.3 record
+    assert!(messages.iter().any(|(sent, ty, _, data)| !sent && *ty == ContentType::Handshake && data[0] == 11));
+    assert!(messages.iter().any(|(_, ty, _, _)| *ty == ContentType::InnerContentType));
+}
+
+#[test]
+fn panicking_callbacks_are_resumed() {
+    let mut client = client_ctx();
+    client.set_info_callback(|_| panic!("info callback panicked"));
+    let payload = panic::catch_unwind(AssertUnwindSafe(|| handshake(&client))).unwrap_err();
+    assert_eq!(panic_message(&*payload), "info callback panicked");
+
+    let mut client = client_ctx();
+    client.set_msg_callback(|_| panic!("msg callback panicked"));
+    let payload = panic::catch_unwind(AssertUnwindSafe(|| handshake(&client))).unwrap_err();
+    assert_eq!(panic_message(&*payload), "msg callback panicked");
+}
+
+/// One span per async operation, however many times it was polled
+#[cfg(all(feature = "tracing", feature = "tokio"))]
+#[test]
+fn async_handshake_has_one_span() {
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+    use openssl_lite::AsyncSsl;
+
+    #[derive(Default)]
+    struct SpanNames(Mutex<Vec<&'static str>>);
+
+    impl Subscriber for SpanNames {
+        fn enabled(&self, _: &Metadata<'_>) -> bool { true }
+        fn new_span(&self, span: &Attributes<'_>) -> Id {
+            let mut names = self.0.lock().unwrap();
+            names.push(span.metadata().name());
+            Id::from_u64(names.len() as u64)
+        }
+        fn record(&self, _: &Id, _: &Record<'_>) {}
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+        fn event(&self, _: &Event<'_>) {}
+        fn enter(&self, _: &Id) {}
+        fn exit(&self, _: &Id) {}
+    }
+
+    let names = Arc::new(SpanNames::default());
+    let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+    tracing::subscriber::with_default(names.clone(), || runtime.block_on(async {
+        // Small buffer, so that both sides are polled many times
+        let (a, b) = tokio::io::duplex(512);
+        let mut server = AsyncSsl::new(&server_ctx(), a).unwrap();
+        let mut client = AsyncSsl::new(&client_ctx(), b).unwrap();
+        client.set_hostname(c"localhost").unwrap();
+        let (s, c) = tokio::join!(server.accept(), client.connect());
+        s.unwrap();
+        c.unwrap();
+        let (s, c) = tokio::join!(server.shutdown_bidirectional(), client.shutdown_bidirectional());
+        s.unwrap();
+        c.unwrap();
+    }));
+
+    let mut names = names.0.lock().unwrap().clone();
+    names.sort();
+    assert_eq!(names, ["tls_accept", "tls_connect", "tls_shutdown", "tls_shutdown"]);
+}
@@ -0,0 +1,173 @@
+//! Verify callbacks over a loopback connection
+
+mod common;
+
+use std::net::Ipv4Addr;
+use std::panic::{self, AssertUnwindSafe};
+
+use openssl_lite::{Ssl, SslCtx, SslError, SslStream, VerifyError, VerifyMode, X509VerifyError};
+
+use common::{serve, accept, connect, client_ctx, server_ctx, server_ctx_with, panic_message, EXPIRED, LEAF_KEY, OTHER, OTHER_KEY};
+
+/// Connects to `host` on a server using `ctx`, expecting the client to reject its certificate
+fn verify_error(ctx: SslCtx, host: &core::ffi::CStr) -> VerifyError {
+    let (sock, server) = serve(move |sock| accept(&ctx, sock).map(|_| ()));
+    let err = match connect(&client_ctx(), host, sock) {
+        Err(SslError::Verify(err)) => err,
+        other => panic!("expected a verify error, got {:?}", other.map(|_| ())),
+    };
+    assert!(matches!(server.join().unwrap(), Err(SslError::PeerAlert(_))));
+    err
+}
+
+#[test]
+fn untrusted_peer_is_a_verify_error() {
+    let err = verify_error(server_ctx_with(OTHER, OTHER_KEY), c"localhost");
+    assert_eq!(err.error(), X509VerifyError::DepthZeroSelfSignedCert);
+    assert_eq!(err.depth(), 0);
+    assert!(err.subject().unwrap().contains("localhost"));
+}
+
+#[test]
+fn expired_peer_is_a_verify_error() {
+    let err = verify_error(server_ctx_with(EXPIRED, LEAF_KEY), c"localhost");
+    assert_eq!(err.error(), X509VerifyError::CertHasExpired);
+    assert_eq!(err.depth(), 0);
+}
+
+#[test]
+fn hostname_mismatch_is_a_verify_error() {
+    let err = verify_error(server_ctx(), c"wrong.test");
+    assert_eq!(err.error(), X509VerifyError::HostnameMismatch);
+    assert_eq!(err.depth(), 0);
+}
+
+#[test]
+fn ip_literal_is_checked_against_ip_san() {
+    let ctx = server_ctx();
+    let (sock, server) = serve(move |sock| accept(&ctx, sock).map(|stream| stream.ssl().servername().map(str::to_owned)));
+    let stream = connect(&client_ctx(), c"127.0.0.1", sock).unwrap();
+    assert_eq!(stream.ssl().verify_result(), Ok(()));
+    // IP addresses are not sent as SNI
+    assert_eq!(server.join().unwrap().unwrap(), None);
+}
+
+#[test]
+fn ip_mismatch_is_a_verify_error() {
+    for host in [c"127.0.0.2", c"::1"] {
+        let err = verify_error(server_ctx(), host);
+        assert_eq!(err.error(), X509VerifyError::IpAddressMismatch);
+        assert_eq!(err.depth(), 0);
+    }
+}
+
+#[test]
+fn verify_ip_replaces_hostname() {
+    let ctx = server_ctx();
+    let (sock, server) = serve(move |sock| accept(&ctx, sock).map(|_| ()));
+    let mut ssl = Ssl::new(&client_ctx()).unwrap();
+    ssl.set_hostname(c"wrong.test").unwrap();
+    ssl.set_verify_ip(Ipv4Addr::LOCALHOST.into()).unwrap();
+    SslStream::new(ssl, sock).unwrap().connect().unwrap();
+    server.join().unwrap().unwrap();
+}
+
+#[test]
+fn callback_sees_each_certificate() {
+    let ctx = server_ctx();
+    let (sock, server) = serve(move |sock| accept(&ctx, sock).map(|_| ()));
+
+    let mut client = client_ctx();
+    let (tx, rx) = std::sync::mpsc::channel();
+    let tx = std::sync::Mutex::new(tx);
+    client.set_verify_callback(move |ok, store| {
+        let cn = store.current_cert().and_then(|cert| cert.subject_name().common_name());
+        tx.lock().unwrap().send((ok, store.error_depth(), cn)).unwrap();
+        ok
+    });
+    connect(&client, c"localhost", sock).unwrap();
+    server.join().unwrap().unwrap();
+
+    let seen: Vec<_> = rx.try_iter().collect();
+    assert_eq!(seen.last(), Some(&(true, 0, Some("localhost".into()))));
+    assert!(seen.iter().all(|&(ok, ..)| ok));
+}
+
+#[test]
+fn callback_can_accept_untrusted_peer() {
+    let ctx = server_ctx_with(OTHER, OTHER_KEY);
+    let (sock, server) = serve(move |sock| accept(&ctx, sock).map(|_| ()));
+
+    let mut client = client_ctx();
+    client.set_verify_callback(|ok, store| ok || store.error() == Some(X509VerifyError::DepthZeroSelfSignedCert));
+    connect(&client, c"localhost", sock).unwrap();
+    server.join().unwrap().unwrap();
+}
+
+#[test]
+fn callback_can_reject_and_set_error() {
+    let ctx = server_ctx();
+    let (sock, server) = serve(move |sock| accept(&ctx, sock).map(|_| ()));
+
+    let mut client = client_ctx();
+    client.set_verify_callback(|_, store| {
+        store.set_error_code(X509VerifyError::CertRevoked.as_raw());
+        false
+    });
+    match connect(&client, c"localhost", sock) {
+        Err(SslError::Verify(err)) => assert_eq!(err.error(), X509VerifyError::CertRevoked),
+        other => panic!("expected a verify error, got {:?}", other.map(|_| ())),
+    }
+    assert!(matches!(server.join().unwrap(), Err(SslError::PeerAlert(_))));
+}
+
+#[test]
+fn ssl_callback_overrides_ctx_callback() {
+    let ctx = server_ctx_with(OTHER, OTHER_KEY);
+    let (sock, server) = serve(move |sock| accept(&ctx, sock).map(|_| ()));
+
+    let mut client = client_ctx();
+    client.set_verify_callback(|_, _| false);
+    let mut ssl = Ssl::new(&client).unwrap();
+    ssl.set_hostname(c"localhost").unwrap();
+    ssl.set_verify_callback(|_, _| true);
+    SslStream::new(ssl, sock).unwrap().connect().unwrap();
+    server.join().unwrap().unwrap();
+}
+
+#[test]
+fn panicking_callback_fails_the_handshake() {
+    let ctx = server_ctx();
+    let (sock, server) = serve(move |sock| accept(&ctx, sock).map(|_| ()));
+
+    let mut client = client_ctx();
+    client.set_verify_callback(|_, _| panic!("verify callback panicked"));
+    let payload = panic::catch_unwind(AssertUnwindSafe(|| connect(&client, c"localhost", sock).map(|_| ()))).unwrap_err();
+    assert_eq!(panic_message(&*payload), "verify callback panicked");
+    assert!(matches!(server.join().unwrap(), Err(SslError::PeerAlert(_))));
+}
+
+#[test]
+fn verified_chain_goes_up_to_the_root() {
+    let ctx = server_ctx();
+    let (sock, server) = serve(move |sock| accept(&ctx, sock).map(|_| ()));
+    let stream = connect(&client_ctx(), c"localhost", sock).unwrap();
+    server.join().unwrap().unwrap();
+
+    let chain: Vec<_> = stream.ssl().verified_chain().iter().map(|cert| cert.subject_name().common_name().unwrap()).collect();
+    assert_eq!(chain, ["localhost", "Test Intermediate", "Test Root"]);
+    assert_eq!(stream.ssl().verify_result(), Ok(()));
+}
+
+#[test]
+fn verify_result_of_untrusted_peer() {
+    let ctx = server_ctx_with(OTHER, OTHER_KEY);
+    let (sock, server) = serve(move |sock| accept(&ctx, sock).map(|_| ()));
+    // Verification runs without failing the handshake
+    let mut client = client_ctx();
+    client.set_verify_mode(VerifyMode::NONE);
+    let stream = connect(&client, c"localhost", sock).unwrap();
+    server.join().unwrap().unwrap();
+
+    assert_eq!(stream.ssl().verify_result(), Err(X509VerifyError::DepthZeroSelfSignedCert));
+}
@@ -0,0 +1,32 @@
+//! Certificate getters
+
+mod common;
+
+use std::time::{Duration, SystemTime};
+
+use openssl_lite::X509;
+
+use common::{CA, EXPIRED, LEAF, NEGATIVE_SERIAL, ZERO_SERIAL};
+
+#[test]
+fn serial_number_keeps_sign() {
+    let serial = |pem| X509::from_pem(pem).unwrap().serial_number().unwrap();
+    assert_eq!(serial(LEAF), [0x10, 0x01]);
+    assert_eq!(serial(ZERO_SERIAL), [0]);
+    // -0x1234
+    assert_eq!(serial(NEGATIVE_SERIAL), [0xed, 0xcc]);
+    // 0x492D... has the high bit clear, 20 bytes as generated
+    assert_eq!(serial(CA).len(), 20);
+}
+
+#[test]
+fn validity_period() {
+    let cert = X509::from_pem(EXPIRED).unwrap();
+    // 2020-01-01 and 2021-01-01, both 00:00:00 UTC
+    assert_eq!(cert.not_before(), Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1577836800)));
+    assert_eq!(cert.not_after(), Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1609459200)));
+
+    let cert = X509::from_pem(LEAF).unwrap();
+    assert!(cert.not_before().unwrap() < SystemTime::now());
+    assert!(cert.not_after().unwrap() > SystemTime::now());
+}